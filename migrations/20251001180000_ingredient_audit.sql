@@ -0,0 +1,8 @@
+CREATE TABLE ingredient_audit (
+    id SERIAL PRIMARY KEY,
+    ingredient_id INTEGER NOT NULL REFERENCES ingredients(id),
+    user_id BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    changes TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
@@ -0,0 +1,46 @@
+ALTER TABLE products
+ADD COLUMN price FLOAT,
+ADD COLUMN shelf_price FLOAT,
+ADD COLUMN package_size TEXT,
+ADD COLUMN ingredient_amount FLOAT;
+
+-- the amounts at report time weren't stored, so the current ones are the best we have
+UPDATE products
+SET
+    ingredient_amount = ingredients.amount,
+    price = products.comparative_price * ingredients.amount
+FROM ingredients
+WHERE ingredients.id = products.ingredient_id;
+
+ALTER TABLE products
+ALTER COLUMN price SET NOT NULL,
+ALTER COLUMN ingredient_amount SET NOT NULL;
+
+DROP VIEW IF EXISTS cheapest_products;
+
+CREATE VIEW cheapest_products AS
+WITH ranked_products AS (
+    SELECT
+      products.id,
+      products.report_id,
+      products.name,
+      products.manufacturer_name,
+      products.comparative_price,
+      products.comparative_price_text,
+      products.url,
+      products.store,
+      products.ingredient_id,
+      products.source,
+      products.price,
+      products.shelf_price,
+      products.package_size,
+      products.ingredient_amount,
+      ROW_NUMBER() OVER (
+        PARTITION BY products.report_id, products.ingredient_id
+        ORDER BY products.price
+      ) AS rn
+    FROM products
+  )
+SELECT *
+FROM ranked_products
+WHERE rn = 1;
@@ -0,0 +1,4 @@
+CREATE TABLE cpi (
+    month DATE PRIMARY KEY,
+    value FLOAT NOT NULL
+);
@@ -0,0 +1,33 @@
+ALTER TABLE products
+ADD COLUMN anomaly BOOLEAN NOT NULL DEFAULT FALSE;
+
+DROP VIEW IF EXISTS cheapest_products;
+
+-- anomalous products are only the cheapest if there's nothing else
+CREATE VIEW cheapest_products AS
+WITH ranked_products AS (
+    SELECT
+      products.id,
+      products.report_id,
+      products.name,
+      products.manufacturer_name,
+      products.comparative_price,
+      products.comparative_price_text,
+      products.url,
+      products.store,
+      products.ingredient_id,
+      products.source,
+      products.price,
+      products.shelf_price,
+      products.package_size,
+      products.ingredient_amount,
+      products.anomaly,
+      ROW_NUMBER() OVER (
+        PARTITION BY products.report_id, products.ingredient_id
+        ORDER BY products.anomaly, products.price
+      ) AS rn
+    FROM products
+  )
+SELECT *
+FROM ranked_products
+WHERE rn = 1;
@@ -0,0 +1 @@
+ALTER TABLE reports ADD COLUMN imported BOOLEAN NOT NULL DEFAULT FALSE;
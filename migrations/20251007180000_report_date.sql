@@ -0,0 +1,24 @@
+ALTER TABLE reports ADD COLUMN date DATE;
+
+UPDATE reports SET date = created_at::date;
+
+-- keep a single report per day, preferring the latest scraped one over imported ones
+DELETE FROM reports
+WHERE id IN (
+    SELECT id
+    FROM (
+        SELECT
+          id,
+          ROW_NUMBER() OVER (
+            PARTITION BY date
+            ORDER BY imported, created_at DESC
+          ) AS rn
+        FROM reports
+      ) ranked_reports
+    WHERE rn > 1
+);
+
+ALTER TABLE reports
+ALTER COLUMN date SET NOT NULL,
+ALTER COLUMN date SET DEFAULT CURRENT_DATE,
+ADD CONSTRAINT reports_date_key UNIQUE (date);
@@ -0,0 +1,10 @@
+CREATE TABLE store_runs (
+    id SERIAL PRIMARY KEY,
+    report_id INTEGER NOT NULL REFERENCES reports(id) ON DELETE CASCADE,
+    store TEXT NOT NULL,
+    status TEXT NOT NULL,
+    error TEXT,
+    duration_ms INTEGER NOT NULL,
+    product_count INTEGER NOT NULL,
+    UNIQUE (report_id, store)
+);
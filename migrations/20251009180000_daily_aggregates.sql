@@ -0,0 +1,28 @@
+-- once a report is older than the retention period its products are rolled up into
+-- these tables and deleted, see `hakan::retention`
+CREATE TABLE daily_totals (
+    report_id INTEGER PRIMARY KEY REFERENCES reports(id) ON DELETE CASCADE,
+    price FLOAT NOT NULL
+);
+
+CREATE TABLE daily_store_totals (
+    report_id INTEGER NOT NULL REFERENCES reports(id) ON DELETE CASCADE,
+    store TEXT NOT NULL,
+    price FLOAT NOT NULL,
+    PRIMARY KEY (report_id, store)
+);
+
+CREATE TABLE daily_ingredient_prices (
+    report_id INTEGER NOT NULL REFERENCES reports(id) ON DELETE CASCADE,
+    ingredient_id INTEGER NOT NULL REFERENCES ingredients(id),
+    price FLOAT NOT NULL,
+    PRIMARY KEY (report_id, ingredient_id)
+);
+
+CREATE TABLE daily_store_ingredient_prices (
+    report_id INTEGER NOT NULL REFERENCES reports(id) ON DELETE CASCADE,
+    store TEXT NOT NULL,
+    ingredient_id INTEGER NOT NULL REFERENCES ingredients(id),
+    price FLOAT NOT NULL,
+    PRIMARY KEY (report_id, store, ingredient_id)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE plot_events (
+    id SERIAL PRIMARY KEY,
+    date DATE NOT NULL,
+    label TEXT NOT NULL,
+    user_id BIGINT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
@@ -0,0 +1,22 @@
+ALTER TABLE ingredients
+    ADD COLUMN color TEXT,
+    ADD COLUMN label TEXT;
+
+UPDATE ingredients SET color = '#ff0000' WHERE name = 'Vetemjöl';
+UPDATE ingredients SET color = '#ff9800' WHERE name = 'Kakao';
+UPDATE ingredients SET color = '#00ff00' WHERE name = 'Ägg';
+UPDATE ingredients SET color = '#0000ff' WHERE name = 'Smör';
+UPDATE ingredients SET color = '#9c27b0' WHERE name = 'Strösocker';
+
+CREATE TABLE stores (
+    id TEXT PRIMARY KEY,
+    label TEXT NOT NULL,
+    color TEXT
+);
+
+INSERT INTO stores (id, label, color) VALUES
+    ('coop', 'Coop', '#00ff00'),
+    ('ica', 'ICA', '#ff0000'),
+    ('willys', 'Willy:s', '#000000'),
+    ('hemkop', 'Hemköp', '#e91e63'),
+    ('mathem', 'Mathem', '#0000ff');
@@ -0,0 +1,62 @@
+use anyhow::{anyhow, Context as _};
+use chrono::Utc;
+use serenity::all::{
+    colours::roles::DARK_GREEN, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, CreateEmbed, EditInteractionResponse, InstallationContext,
+};
+
+use super::{get_str, parse_date};
+use crate::{hakan, AppState};
+
+/// Discord's limit for embed descriptions.
+const MAX_LEN: usize = 4096;
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("håkanjämför")
+        .description("Jämför två dagars håkanrapporter.")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "från",
+                "Det första datumet (ÅÅÅÅ-MM-DD).",
+            )
+            .required(true),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "till",
+            "Det andra datumet (ÅÅÅÅ-MM-DD). Idag om det inte anges.",
+        ))
+        .add_integration_type(InstallationContext::User)
+}
+
+#[tracing::instrument]
+pub async fn run(
+    interaction: &CommandInteraction,
+    ctx: &Context,
+    state: &AppState,
+) -> anyhow::Result<()> {
+    interaction.defer(&ctx.http).await?;
+
+    let options = &interaction.data.options;
+    let from = parse_date(get_str(options, "från").context("från must be given")?)?;
+    let to = match get_str(options, "till") {
+        Some(date) => parse_date(date)?,
+        None => Utc::now().date_naive(),
+    };
+
+    let diff = hakan::diff::between(from, to, state)
+        .await?
+        .ok_or_else(|| anyhow!("no reports on or before both dates"))?;
+
+    let embed = CreateEmbed::new()
+        .title(format!("🔀 Håkanrapporten {} → {}", diff.from, diff.to))
+        .color(DARK_GREEN)
+        .description(diff.render(MAX_LEN));
+
+    interaction
+        .edit_response(&ctx.http, EditInteractionResponse::new().add_embed(embed))
+        .await?;
+
+    Ok(())
+}
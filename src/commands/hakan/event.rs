@@ -0,0 +1,154 @@
+use anyhow::{bail, Context as _};
+use serenity::all::{
+    colours::roles::DARK_GREEN, CommandDataOption, CommandDataOptionValue, CommandInteraction,
+    CommandOptionType, Context, CreateCommand, CreateCommandOption, CreateEmbed,
+    EditInteractionResponse, Permissions,
+};
+
+use super::{get_option, get_str, parse_date};
+use crate::{
+    hakan::db::{self, Range},
+    AppState,
+};
+
+/// Discord's limit for embed descriptions.
+const MAX_LEN: usize = 4096;
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("håkanhändelse")
+        .description("Administrera händelserna som markeras i håkangraferna.")
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "lista",
+            "Visa alla händelser.",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "lägg-till",
+                "Lägg till en händelse.",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "datum",
+                    "När det hände (ÅÅÅÅ-MM-DD).",
+                )
+                .required(true),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "namn",
+                    "Vad som hände, t.ex. Smörkris.",
+                )
+                .max_length(40)
+                .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "ta-bort",
+                "Ta bort en händelse.",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "id", "Händelsens id.")
+                    .required(true),
+            ),
+        )
+}
+
+#[tracing::instrument]
+pub async fn run(
+    interaction: &CommandInteraction,
+    ctx: &Context,
+    state: &AppState,
+) -> anyhow::Result<()> {
+    interaction.defer(&ctx.http).await?;
+
+    let subcommand = &interaction.data.options[0];
+    let CommandDataOptionValue::SubCommand(options) = &subcommand.value else {
+        bail!("expected a subcommand");
+    };
+
+    let embed = match subcommand.name.as_str() {
+        "lista" => list(state).await?,
+        "lägg-till" => add(options, interaction.user.id.get() as i64, state).await?,
+        "ta-bort" => remove(options, state).await?,
+        _ => bail!("unknown subcommand"),
+    };
+
+    interaction
+        .edit_response(&ctx.http, EditInteractionResponse::new().add_embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+async fn list(state: &AppState) -> anyhow::Result<CreateEmbed> {
+    let events = db::plot_events(&Range::default(), state).await?;
+
+    let mut description = String::new();
+
+    for (i, event) in events.iter().enumerate() {
+        let line = format!("`{}` {}: {}\n", event.id, event.date, event.label);
+
+        // leave room for the "och n till" line
+        if description.chars().count() + line.chars().count() + 32 > MAX_LEN {
+            description.push_str(&format!("-# … och {} till", events.len() - i));
+            break;
+        }
+
+        description.push_str(&line);
+    }
+
+    if events.is_empty() {
+        description.push_str("Det finns inga händelser än.");
+    }
+
+    Ok(CreateEmbed::new()
+        .color(DARK_GREEN)
+        .title("📌 Håkanhändelser")
+        .description(description))
+}
+
+async fn add(
+    options: &[CommandDataOption],
+    user_id: i64,
+    state: &AppState,
+) -> anyhow::Result<CreateEmbed> {
+    let date = parse_date(get_str(options, "datum").context("datum must be given")?)?;
+    let label = get_str(options, "namn")
+        .context("namn must be given")?
+        .trim();
+
+    if label.is_empty() {
+        bail!("the event needs a name");
+    }
+
+    let id = db::insert_plot_event(date, label, user_id, state).await?;
+    state.plot_cache.clear();
+
+    Ok(CreateEmbed::new()
+        .color(DARK_GREEN)
+        .title(format!("📌 La till {label}"))
+        .description(format!("Markeras {date} i graferna. Id: `{id}`")))
+}
+
+async fn remove(options: &[CommandDataOption], state: &AppState) -> anyhow::Result<CreateEmbed> {
+    let id = get_option(options, "id")
+        .and_then(CommandDataOptionValue::as_i64)
+        .context("id must be given")?;
+
+    let event = db::delete_plot_event(id as i32, state)
+        .await?
+        .with_context(|| format!("there is no event with id {id}"))?;
+    state.plot_cache.clear();
+
+    Ok(CreateEmbed::new()
+        .color(DARK_GREEN)
+        .title(format!("🗑️ Tog bort {}", event.label))
+        .description(format!("Markerades {}.", event.date)))
+}
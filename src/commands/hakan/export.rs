@@ -0,0 +1,69 @@
+use anyhow::{bail, Context as _};
+use serenity::all::{
+    CommandDataOptionValue, CommandInteraction, CommandOptionType, Context, CreateAttachment,
+    CreateCommand, CreateCommandOption, EditInteractionResponse,
+};
+
+use super::{get_str, parse_range, with_range_options};
+use crate::{
+    hakan::export::{self, Format, Series},
+    AppState,
+};
+
+pub fn register() -> CreateCommand {
+    let series = [
+        (Series::Total, "Exportera totalpriset per dag."),
+        (Series::Store, "Exportera totalpriset per butik och dag."),
+        (Series::Ingredient, "Exportera ingredienspriset per dag."),
+        (Series::Products, "Exportera alla sparade produkter."),
+    ];
+
+    let mut command =
+        CreateCommand::new("håkanexport").description("Exportera håkandata som en fil.");
+
+    for (series, description) in series {
+        let format = CreateCommandOption::new(CommandOptionType::String, "format", "Filformatet.")
+            .add_string_choice("CSV", "csv")
+            .add_string_choice("JSON", "json");
+
+        let option =
+            CreateCommandOption::new(CommandOptionType::SubCommand, series.id(), description)
+                .add_sub_option(format);
+
+        command = command.add_option(with_range_options(option));
+    }
+
+    command
+}
+
+#[tracing::instrument]
+pub async fn run(
+    interaction: &CommandInteraction,
+    ctx: &Context,
+    state: &AppState,
+) -> anyhow::Result<()> {
+    interaction.defer(&ctx.http).await?;
+
+    let subcommand = &interaction.data.options[0];
+    let CommandDataOptionValue::SubCommand(options) = &subcommand.value else {
+        bail!("expected a subcommand");
+    };
+
+    let series = Series::from_id(&subcommand.name).context("unknown series")?;
+    let format = match get_str(options, "format") {
+        Some(format) => Format::from_id(format).context("unknown format")?,
+        None => Format::Csv,
+    };
+    let range = parse_range(options)?;
+
+    let data = export::export(series, format, &range, state).await?;
+    let file_name = format!("håkan_{}.{}", series.id(), format.extension());
+
+    let response = EditInteractionResponse::new()
+        .content("📊 Här är din export!")
+        .new_attachment(CreateAttachment::bytes(data, file_name));
+
+    interaction.edit_response(&ctx.http, response).await?;
+
+    Ok(())
+}
@@ -0,0 +1,414 @@
+use anyhow::{anyhow, bail, Context as _};
+use serenity::all::{
+    colours::roles::DARK_GREEN, CommandDataOption, CommandDataOptionValue, CommandInteraction,
+    CommandOptionType, Context, CreateCommand, CreateCommandOption, CreateEmbed,
+    EditInteractionResponse, Permissions,
+};
+
+use super::{get_f64, get_option, get_str};
+use crate::{
+    hakan::{self, Ingredient, ProductSource, Store},
+    AppState,
+};
+
+/// Given instead of a colour or label to remove it.
+const CLEAR: &str = "-";
+
+pub fn register() -> CreateCommand {
+    let mut add = CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "lägg-till",
+        "Lägg till en ny ingrediens.",
+    )
+    .add_sub_option(name_option())
+    .add_sub_option(amount_option().required(true));
+
+    for store in Store::ALL {
+        add = add.add_sub_option(category_option(store).required(true));
+    }
+
+    let mut edit = CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "ändra",
+        "Ändra en befintlig ingrediens.",
+    )
+    .add_sub_option(name_option())
+    .add_sub_option(amount_option())
+    .add_sub_option(aliases_option());
+
+    for store in Store::ALL {
+        edit = edit.add_sub_option(category_option(store));
+    }
+
+    CreateCommand::new("håkaningrediens")
+        .description("Administrera håkaningredienserna.")
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "lista",
+            "Visa alla ingredienser.",
+        ))
+        .add_option(
+            add.add_sub_option(aliases_option())
+                .add_sub_option(color_option())
+                .add_sub_option(label_option()),
+        )
+        .add_option(
+            edit.add_sub_option(color_option())
+                .add_sub_option(label_option()),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "kategorier",
+                "Sök efter kategori-id:n hos en butik.",
+            )
+            .add_sub_option(store_option())
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "sökord",
+                    "Vad kategorin ska heta eller innehålla, t.ex. smör.",
+                )
+                .required(true),
+            ),
+        )
+}
+
+fn store_option() -> CreateCommandOption {
+    let mut option =
+        CreateCommandOption::new(CommandOptionType::String, "butik", "Butiken att söka i.")
+            .required(true);
+
+    for store in Store::ALL {
+        option = option.add_string_choice(store.to_string(), store.id());
+    }
+
+    option
+}
+
+fn name_option() -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::String, "namn", "Ingrediensens namn.")
+        .required(true)
+}
+
+fn amount_option() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::Number,
+        "mängd",
+        "Mängd i receptet, i samma enhet som jämförpriset (kg eller st).",
+    )
+    .min_number_value(0.0)
+}
+
+fn aliases_option() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::String,
+        "alias",
+        "Kommaseparerade alternativa produktnamn.",
+    )
+}
+
+fn color_option() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::String,
+        "färg",
+        "Färgen i graferna, t.ex. #ff9800. Ange - för att ta bort den.",
+    )
+}
+
+fn label_option() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::String,
+        "etikett",
+        "Namnet som visas i graferna, om det ska vara ett annat. Ange - för att ta bort det.",
+    )
+}
+
+fn category_option(store: Store) -> CreateCommandOption {
+    let kind = match store {
+        Store::Coop => CommandOptionType::Integer,
+        _ => CommandOptionType::String,
+    };
+
+    CreateCommandOption::new(kind, store.id(), format!("Kategori-id hos {store}."))
+}
+
+#[tracing::instrument]
+pub async fn run(
+    interaction: &CommandInteraction,
+    ctx: &Context,
+    state: &AppState,
+) -> anyhow::Result<()> {
+    interaction.defer(&ctx.http).await?;
+
+    let subcommand = &interaction.data.options[0];
+    let CommandDataOptionValue::SubCommand(options) = &subcommand.value else {
+        bail!("expected a subcommand");
+    };
+
+    let user_id = interaction.user.id.get() as i64;
+
+    let embed = match subcommand.name.as_str() {
+        "lista" => list(state).await?,
+        "lägg-till" => add(options, user_id, state).await?,
+        "ändra" => edit(options, user_id, state).await?,
+        "kategorier" => discover(options, state).await?,
+        _ => bail!("unknown subcommand"),
+    };
+
+    interaction
+        .edit_response(&ctx.http, EditInteractionResponse::new().add_embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+async fn list(state: &AppState) -> anyhow::Result<CreateEmbed> {
+    let ingredients = hakan::db::ingredients(state).await?;
+
+    let fields = ingredients.iter().map(|ingredient| {
+        (
+            format!("{} `{}`", ingredient.name, ingredient.amount),
+            describe(ingredient),
+            false,
+        )
+    });
+
+    Ok(CreateEmbed::new()
+        .color(DARK_GREEN)
+        .title("Håkaningredienser")
+        .fields(fields))
+}
+
+async fn add(
+    options: &[CommandDataOption],
+    user_id: i64,
+    state: &AppState,
+) -> anyhow::Result<CreateEmbed> {
+    let name = get_str(options, "namn").context("name is required")?;
+
+    if hakan::db::ingredient_by_name(state, name).await?.is_some() {
+        bail!("an ingredient named {name} already exists");
+    }
+
+    let mut ingredient = Ingredient {
+        id: 0,
+        name: name.to_string(),
+        aliases: get_str(options, "alias").unwrap_or_default().to_string(),
+        amount: get_f64(options, "mängd").context("amount is required")?,
+        coop_id: 0,
+        ica_category_name: String::new(),
+        willys_category_name: String::new(),
+        hemkop_category_name: String::new(),
+        mathem_category_name: String::new(),
+        color: get_color(options)?.flatten(),
+        label: get_label(options).flatten(),
+    };
+
+    for store in Store::ALL {
+        let category =
+            get_category(options, store).with_context(|| format!("{store} is required"))?;
+        ingredient.set_category(store, category)?;
+    }
+
+    validate(&ingredient, &Store::ALL, state).await?;
+
+    hakan::db::insert_ingredient(&ingredient, user_id, state).await?;
+    state.plot_cache.clear();
+
+    Ok(CreateEmbed::new()
+        .color(DARK_GREEN)
+        .title(format!("☀️ La till {}", ingredient.name))
+        .description(describe(&ingredient)))
+}
+
+async fn edit(
+    options: &[CommandDataOption],
+    user_id: i64,
+    state: &AppState,
+) -> anyhow::Result<CreateEmbed> {
+    let name = get_str(options, "namn").context("name is required")?;
+
+    let old = hakan::db::ingredient_by_name(state, name)
+        .await?
+        .ok_or_else(|| anyhow!("no ingredient named {name}"))?;
+
+    let mut ingredient = old.clone();
+    let mut changes = Vec::new();
+
+    // options that are given but match the current value aren't changes
+    if let Some(amount) = get_f64(options, "mängd").filter(|amount| *amount != old.amount) {
+        changes.push(format!("mängd: {} → {amount}", old.amount));
+        ingredient.amount = amount;
+    }
+
+    if let Some(aliases) = get_str(options, "alias").filter(|aliases| *aliases != old.aliases) {
+        changes.push(format!("alias: {} → {aliases}", old.aliases));
+        ingredient.aliases = aliases.to_string();
+    }
+
+    if let Some(color) = get_color(options)?.filter(|color| *color != old.color) {
+        changes.push(format!(
+            "färg: {} → {}",
+            or_clear(&old.color),
+            or_clear(&color)
+        ));
+        ingredient.color = color;
+    }
+
+    if let Some(label) = get_label(options).filter(|label| *label != old.label) {
+        changes.push(format!(
+            "etikett: {} → {}",
+            or_clear(&old.label),
+            or_clear(&label)
+        ));
+        ingredient.label = label;
+    }
+
+    let mut changed_stores = Vec::new();
+
+    for store in Store::ALL {
+        if let Some(category) =
+            get_category(options, store).filter(|category| *category != old.category(store))
+        {
+            changes.push(format!(
+                "{}: {} → {category}",
+                store.id(),
+                old.category(store)
+            ));
+            ingredient.set_category(store, category)?;
+            changed_stores.push(store);
+        }
+    }
+
+    if changes.is_empty() {
+        bail!("nothing changed");
+    }
+
+    if ingredient.aliases != old.aliases {
+        // aliases decide which products match, so every store needs to be rechecked
+        changed_stores = Store::ALL.to_vec();
+    }
+
+    validate(&ingredient, &changed_stores, state).await?;
+
+    let changes = changes.join("\n");
+    hakan::db::update_ingredient(&ingredient, &changes, user_id, state).await?;
+    state.plot_cache.clear();
+
+    Ok(CreateEmbed::new()
+        .color(DARK_GREEN)
+        .title(format!("☀️ Ändrade {}", ingredient.name))
+        .description(changes))
+}
+
+async fn discover(options: &[CommandDataOption], state: &AppState) -> anyhow::Result<CreateEmbed> {
+    let store = get_str(options, "butik")
+        .and_then(Store::from_id)
+        .context("store is required")?;
+    let keyword = get_str(options, "sökord").context("keyword is required")?;
+
+    let candidates = hakan::discover_categories(store, keyword, state).await?;
+
+    let fields = candidates.into_iter().map(|candidate| {
+        (
+            candidate.name,
+            format!("`{}`\n{}", candidate.id, candidate.samples.join("\n")),
+            false,
+        )
+    });
+
+    Ok(CreateEmbed::new()
+        .color(DARK_GREEN)
+        .title(format!("Kategorier hos {store} för \"{keyword}\""))
+        .fields(fields))
+}
+
+/// Fetches the ingredient from each of `stores` to make sure the category ids are valid.
+async fn validate(
+    ingredient: &Ingredient,
+    stores: &[Store],
+    state: &AppState,
+) -> anyhow::Result<()> {
+    for store in stores {
+        let product = hakan::fetch_product(*store, ingredient, state)
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to fetch {} from {store} with category {}",
+                    ingredient.name,
+                    ingredient.category(*store)
+                )
+            })?;
+
+        // fetching falls back to searching, which we don't want to accept here
+        if product.source != ProductSource::Category {
+            bail!(
+                "no matching products in {store} category {}",
+                ingredient.category(*store)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn describe(ingredient: &Ingredient) -> String {
+    let mut lines = Store::ALL
+        .iter()
+        .map(|store| format!("{store}: `{}`", ingredient.category(*store)))
+        .collect::<Vec<_>>();
+
+    if !ingredient.aliases.is_empty() {
+        lines.insert(0, format!("Alias: `{}`", ingredient.aliases));
+    }
+
+    if let Some(color) = &ingredient.color {
+        lines.push(format!("Färg: `{color}`"));
+    }
+
+    if let Some(label) = &ingredient.label {
+        lines.push(format!("Etikett: `{label}`"));
+    }
+
+    lines.join("\n")
+}
+
+fn get_category(options: &[CommandDataOption], store: Store) -> Option<String> {
+    match get_option(options, store.id())? {
+        CommandDataOptionValue::Integer(id) => Some(id.to_string()),
+        CommandDataOptionValue::String(category) => Some(category.clone()),
+        _ => None,
+    }
+}
+
+/// An optional setting that can be removed by giving [`CLEAR`] instead of a value.
+/// Returns `Some(None)` if it should be removed.
+fn get_clearable<'a>(options: &'a [CommandDataOption], name: &str) -> Option<Option<&'a str>> {
+    get_str(options, name).map(|value| (value != CLEAR).then_some(value))
+}
+
+fn get_color(options: &[CommandDataOption]) -> anyhow::Result<Option<Option<String>>> {
+    let Some(color) = get_clearable(options, "färg") else {
+        return Ok(None);
+    };
+
+    let Some(color) = color else {
+        return Ok(Some(None));
+    };
+
+    if hakan::plot::parse_color(color).is_none() {
+        bail!("invalid color {color}, expected #rrggbb");
+    }
+
+    Ok(Some(Some(color.to_lowercase())))
+}
+
+fn get_label(options: &[CommandDataOption]) -> Option<Option<String>> {
+    get_clearable(options, "etikett").map(|label| label.map(str::to_string))
+}
+
+fn or_clear(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or(CLEAR)
+}
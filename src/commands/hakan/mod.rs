@@ -1,4 +1,137 @@
+use anyhow::{bail, Context as _};
+use chrono::NaiveDate;
+use serenity::all::{
+    CommandDataOption, CommandDataOptionValue, CommandOptionType, CreateCommandOption,
+};
+
+use crate::hakan::{
+    db::Range,
+    plot::{ImageFormat, PlotStyle, Theme, MAX_SIZE, MIN_SIZE},
+};
+
+pub mod compare;
+pub mod event;
+pub mod export;
+pub mod ingredient;
 pub mod recipe;
 pub mod role;
+pub mod shopping;
+pub mod status;
 pub mod stock;
 pub mod wr;
+
+pub fn get_option<'a>(
+    options: &'a [CommandDataOption],
+    name: &str,
+) -> Option<&'a CommandDataOptionValue> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .map(|option| &option.value)
+}
+
+pub fn get_str<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a str> {
+    get_option(options, name).and_then(CommandDataOptionValue::as_str)
+}
+
+pub fn get_f64(options: &[CommandDataOption], name: &str) -> Option<f64> {
+    get_option(options, name).and_then(CommandDataOptionValue::as_f64)
+}
+
+pub fn parse_date(date: &str) -> anyhow::Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .with_context(|| format!("invalid date {date}, expected YYYY-MM-DD"))
+}
+
+pub fn with_range_options(option: CreateCommandOption) -> CreateCommandOption {
+    option
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "från",
+            "Första datumet att visa, t.ex. 2025-04-10.",
+        ))
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "till",
+            "Sista datumet att visa, t.ex. 2025-12-24.",
+        ))
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "period",
+                "Hur långt tillbaka att visa.",
+            )
+            .add_string_choice("Senaste veckan", "vecka")
+            .add_string_choice("Senaste månaden", "månad")
+            .add_string_choice("Senaste året", "år")
+            .add_string_choice("Allt", "allt"),
+        )
+}
+
+pub fn parse_range(options: &[CommandDataOption]) -> anyhow::Result<Range> {
+    let mut range = match get_str(options, "period") {
+        Some("vecka") => Range::last_week(),
+        Some("månad") => Range::last_month(),
+        Some("år") => Range::last_year(),
+        Some("allt") | None => Range::default(),
+        Some(period) => bail!("unknown period: {period}"),
+    };
+
+    let dates = Range::from_dates(get_str(options, "från"), get_str(options, "till"))?;
+    range.from = dates.from.or(range.from);
+    range.to = dates.to.or(range.to);
+
+    Ok(range)
+}
+
+pub fn with_style_options(option: CreateCommandOption) -> CreateCommandOption {
+    option
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::String, "tema", "Hur grafen ska se ut.")
+                .add_string_choice("Ljust", Theme::Light.id())
+                .add_string_choice("Mörkt", Theme::Dark.id()),
+        )
+        .add_sub_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "svg",
+            "Rita grafen som SVG istället för PNG.",
+        ))
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "bredd",
+                "Grafens bredd i pixlar.",
+            )
+            .min_int_value(MIN_SIZE.0.into())
+            .max_int_value(MAX_SIZE.0.into()),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "höjd", "Grafens höjd i pixlar.")
+                .min_int_value(MIN_SIZE.1.into())
+                .max_int_value(MAX_SIZE.1.into()),
+        )
+}
+
+pub fn parse_style(options: &[CommandDataOption]) -> anyhow::Result<PlotStyle> {
+    let mut style = PlotStyle::configured();
+
+    if let Some(theme) = get_str(options, "tema") {
+        match Theme::from_id(theme) {
+            Some(theme) => style.theme = theme,
+            None => bail!("unknown theme: {theme}"),
+        }
+    }
+
+    if get_option(options, "svg").and_then(CommandDataOptionValue::as_bool) == Some(true) {
+        style.format = ImageFormat::Svg;
+    }
+
+    let width = get_option(options, "bredd")
+        .and_then(CommandDataOptionValue::as_i64)
+        .map_or(style.width, |width| width as u32);
+    let height = get_option(options, "höjd")
+        .and_then(CommandDataOptionValue::as_i64)
+        .map_or(style.height, |height| height as u32);
+
+    Ok(style.with_size(width, height))
+}
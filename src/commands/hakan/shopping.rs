@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Context as _};
+use serenity::all::{
+    ButtonStyle, Color, CommandDataOptionValue, CommandInteraction, CommandOptionType,
+    ComponentInteraction, Context, CreateActionRow, CreateButton, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateMessage, EditInteractionResponse,
+};
+
+use crate::{
+    hakan::{self, optimize::Penalty},
+    AppState,
+};
+
+pub const DM_BUTTON_ID: &str = "håkanhandla_dm";
+
+pub fn register() -> CreateCommand {
+    let default = Penalty::default();
+
+    CreateCommand::new("håkanhandla")
+        .description("Räkna ut var du ska handla för att få billigast möjliga håkan.")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Number,
+                "straff",
+                format!(
+                    "Extra kostnad per ytterligare butik du besöker (standard {}kr).",
+                    default.per_store
+                ),
+            )
+            .min_number_value(0.0),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Number,
+                "frakt",
+                format!(
+                    "Fraktavgift för nätbutiker som Mathem (standard {}kr).",
+                    default.delivery_fee
+                ),
+            )
+            .min_number_value(0.0),
+        )
+}
+
+#[tracing::instrument]
+pub async fn run(
+    interaction: &CommandInteraction,
+    ctx: &Context,
+    state: &AppState,
+) -> anyhow::Result<()> {
+    interaction.defer(&ctx.http).await?;
+
+    let mut penalty = Penalty::default();
+
+    for option in &interaction.data.options {
+        if let CommandDataOptionValue::Number(value) = option.value {
+            match option.name.as_str() {
+                "straff" => penalty.per_store = value,
+                "frakt" => penalty.delivery_fee = value,
+                _ => (),
+            }
+        }
+    }
+
+    let report = hakan::load_last_report(state)
+        .await?
+        .context("there are no reports yet")?;
+    let list = hakan::optimize::optimize(&report, &penalty)
+        .ok_or_else(|| anyhow!("no stores available"))?;
+
+    let fields = list.stores.iter().map(|(store, products)| {
+        let subtotal: f64 = products.iter().map(|(_, product)| product.price).sum();
+
+        let lines = products
+            .iter()
+            .map(|(ingredient, product)| {
+                format!(
+                    "{} `{:0.1}kr`: [{} {}]({})",
+                    ingredient.name,
+                    product.price,
+                    product.manufacturer_name,
+                    product.name,
+                    product.url
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        (format!("{store} `{subtotal:0.1}kr`"), lines, false)
+    });
+
+    let embed = CreateEmbed::new()
+        .title("🛒🍰 Håkans inköpslista 🍰🛒")
+        .color(Color::DARK_GREEN)
+        .description(format!(
+            "# `{:0.1}kr`\nVaror `{:0.1}kr` + straff och frakt `{:0.1}kr`\n-# Priserna kommer från den senaste håkanrapporten.",
+            list.total_price(),
+            list.product_price,
+            list.penalty
+        ))
+        .fields(fields);
+
+    let button = CreateButton::new(DM_BUTTON_ID)
+        .label("Skicka till mig")
+        .emoji('📩')
+        .style(ButtonStyle::Secondary);
+
+    let response = EditInteractionResponse::new()
+        .add_embed(embed)
+        .components(vec![CreateActionRow::Buttons(vec![button])]);
+
+    interaction.edit_response(&ctx.http, response).await?;
+
+    Ok(())
+}
+
+/// Sends the shopping list in the clicked message to the user who clicked.
+#[tracing::instrument]
+pub async fn send_dm(interaction: &ComponentInteraction, ctx: &Context) -> anyhow::Result<()> {
+    let embeds = interaction
+        .message
+        .embeds
+        .iter()
+        .cloned()
+        .map(CreateEmbed::from)
+        .collect();
+
+    interaction
+        .user
+        .direct_message(&ctx.http, CreateMessage::new().embeds(embeds))
+        .await?;
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("📩 Skickade inköpslistan till dig!")
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use serenity::all::{
+    colours::roles::DARK_GREEN, CommandInteraction, Context, CreateCommand, CreateEmbed,
+    EditInteractionResponse, Permissions,
+};
+
+use crate::{
+    hakan::{db, Store},
+    AppState,
+};
+
+/// How far back failures are counted.
+const FAILURE_DAYS: i64 = 30;
+/// Errors can contain whole response bodies, which wouldn't fit in a field.
+const MAX_ERROR_LEN: usize = 300;
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("håkanstatus")
+        .description("Visa hur det gick att hämta priser från butikerna senast.")
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+}
+
+#[tracing::instrument]
+pub async fn run(
+    interaction: &CommandInteraction,
+    ctx: &Context,
+    state: &AppState,
+) -> anyhow::Result<()> {
+    interaction.defer(&ctx.http).await?;
+
+    let runs = db::last_store_runs(state).await?;
+    let failures = db::store_failures(FAILURE_DAYS, state).await?;
+
+    let fields = runs.iter().map(|run| {
+        let name = Store::from_id(&run.store)
+            .map(|store| store.to_string())
+            .unwrap_or_else(|| run.store.clone());
+
+        let status = match &run.error {
+            Some(error) => format!("❌ misslyckades: `{}`", truncate_error(error)),
+            None => format!("✅ {} produkter", run.product_count),
+        };
+
+        let failure_count = failures
+            .iter()
+            .find(|(store, _)| *store == run.store)
+            .map(|(_, count)| *count)
+            .unwrap_or(0);
+
+        (
+            name,
+            format!(
+                "{status}\n{:.1}s\n-# {failure_count} misslyckade de senaste {FAILURE_DAYS} dagarna",
+                run.duration_ms as f64 / 1000.0
+            ),
+            true,
+        )
+    });
+
+    let description = match runs.first() {
+        Some(run) => format!("Senaste rapporten: {}", run.date),
+        None => "Det finns ingen rapport med butiksstatus än.".to_string(),
+    };
+
+    let embed = CreateEmbed::new()
+        .title("🏪 Håkanstatus")
+        .color(DARK_GREEN)
+        .description(description)
+        .fields(fields);
+
+    interaction
+        .edit_response(&ctx.http, EditInteractionResponse::new().add_embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// The first line of `error`, cut off at [`MAX_ERROR_LEN`] characters.
+fn truncate_error(error: &str) -> String {
+    // backticks would end the code span early
+    let line = error.lines().next().unwrap_or_default().replace('`', "'");
+
+    if line.chars().count() > MAX_ERROR_LEN {
+        let truncated = line.chars().take(MAX_ERROR_LEN - 1).collect::<String>();
+        format!("{truncated}…")
+    } else {
+        line
+    }
+}
@@ -1,29 +1,109 @@
-use anyhow::bail;
+use anyhow::{bail, Context as _};
+use chrono::{Datelike, TimeDelta, Utc};
 use serenity::all::{
-    colours::roles::DARK_GREEN, CommandInteraction, CommandOptionType, Context, CreateCommand,
-    CreateCommandOption, CreateEmbed, EditInteractionResponse, InstallationContext,
+    colours::roles::DARK_GREEN, CommandDataOptionValue, CommandInteraction, CommandOptionType,
+    Context, CreateCommand, CreateCommandOption, CreateEmbed, EditInteractionResponse,
+    InstallationContext,
 };
 
-use crate::{hakan, AppState};
+use super::{
+    get_option, get_str, parse_range, parse_style, with_range_options, with_style_options,
+};
+use crate::{
+    hakan::{
+        self,
+        plot::{ImageFormat, Plot, PlotStyle},
+    },
+    AppState,
+};
 
 pub fn register() -> CreateCommand {
     CreateCommand::new("håkankurs")
         .description("Visa håkankursen.")
-        .add_option(CreateCommandOption::new(
-            CommandOptionType::SubCommand,
-            "total",
-            "Visa totalpris över tid.",
-        ))
-        .add_option(CreateCommandOption::new(
+        .add_option(
+            with_style_options(with_range_options(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "total",
+                "Visa totalpris över tid.",
+            )))
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "prognos",
+                    "Visa en prognos för så här många dagar framåt.",
+                )
+                .min_int_value(7)
+                .max_int_value(30),
+            ),
+        )
+        .add_option(with_style_options(with_range_options(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "butik",
+                "Visa totalpris per butik över tid.",
+            ),
+        )))
+        .add_option(
+            with_style_options(with_range_options(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "ingrediens",
+                "Visa ingredienspris över tid.",
+            )))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "namn",
+                "Visa bara den här ingrediensen, med en linje per butik.",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "per-butik",
+                "Visa en ruta per ingrediens med en linje per butik.",
+            )),
+        )
+        .add_option(
+            with_style_options(with_range_options(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "fördelning",
+                "Visa hur mycket varje ingrediens bidrar till totalpriset.",
+            )))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "procent",
+                "Visa ingrediensernas andel av totalpriset i procent.",
+            )),
+        )
+        .add_option(
+            with_style_options(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "idag",
+                "Jämför butikerna i den senaste rapporten.",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "total",
+                "Visa bara hela korgens pris istället för varje ingrediens.",
+            )),
+        )
+        .add_option(
+            with_style_options(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "kalender",
+                "Visa hur håkankursen ändrades varje dag under ett år.",
+            ))
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "år",
+                    "Året att visa. I år om det inte anges.",
+                )
+                .min_int_value(2025),
+            ),
+        )
+        .add_option(with_style_options(CreateCommandOption::new(
             CommandOptionType::SubCommand,
-            "butik",
-            "Visa totalpris per butik över tid.",
-        ))
-        .add_option(CreateCommandOption::new(
-            CommandOptionType::SubCommand,
-            "ingrediens",
-            "Visa ingredienspris över tid.",
-        ))
+            "inflation",
+            "Jämför håkankursen med livsmedelsinflationen.",
+        )))
         .add_option(CreateCommandOption::new(
             CommandOptionType::SubCommand,
             "uppdatera",
@@ -41,22 +121,133 @@ pub async fn run(
     interaction.defer(&ctx.http).await?;
 
     let command = &interaction.data.options[0].name;
+    let options = match &interaction.data.options[0].value {
+        CommandDataOptionValue::SubCommand(options) => options.as_slice(),
+        _ => &[],
+    };
+    let range = parse_range(options)?;
+    let style = parse_style(options)?;
 
     //let report = hakan::create_report(state).await?;
 
-    let (title, plot_url) = match command.as_str() {
-        "total" => (
-            "Håkankursen",
-            hakan::plot::create_total(state, false).await?,
-        ),
+    let (title, plot) = match command.as_str() {
+        "total" => {
+            let forecast = match get_option(options, "prognos").and_then(|days| days.as_i64()) {
+                Some(days) => hakan::forecast::total(days, state).await?,
+                None => None,
+            };
+
+            (
+                "Håkankursen",
+                hakan::plot::create_total(state, &range, forecast.as_ref(), &style, false).await?,
+            )
+        }
         "butik" => (
             "Håkankurs per butik",
-            hakan::plot::create_by_store(state, false).await?,
-        ),
-        "ingrediens" => (
-            "Håkankurs per ingrediens",
-            hakan::plot::create_by_ingredient(state, false).await?,
+            hakan::plot::create_by_store(state, &range, &style, false).await?,
         ),
+        "ingrediens" => match get_str(options, "namn") {
+            Some(name) => {
+                let ingredient = hakan::db::ingredient_by_name(state, name)
+                    .await?
+                    .with_context(|| format!("unknown ingredient {name}"))?;
+
+                (
+                    "Håkankurs för en ingrediens",
+                    hakan::plot::create_by_ingredient_and_store(
+                        state,
+                        &range,
+                        Some(&ingredient.name),
+                        &style,
+                        false,
+                    )
+                    .await?,
+                )
+            }
+            None if get_option(options, "per-butik").and_then(|value| value.as_bool())
+                == Some(true) =>
+            {
+                (
+                    "Håkankurs per ingrediens och butik",
+                    hakan::plot::create_by_ingredient_and_store(state, &range, None, &style, false)
+                        .await?,
+                )
+            }
+            None => (
+                "Håkankurs per ingrediens",
+                hakan::plot::create_by_ingredient(state, &range, &style, false).await?,
+            ),
+        },
+        "fördelning" => {
+            let share =
+                get_option(options, "procent").and_then(|value| value.as_bool()) == Some(true);
+
+            (
+                "Håkankursen per ingrediens",
+                hakan::plot::create_contribution(state, &range, share, &style, false).await?,
+            )
+        }
+        "idag" => {
+            let report = hakan::load_last_report(state)
+                .await?
+                .context("there are no reports yet")?;
+            let by_ingredient =
+                get_option(options, "total").and_then(|value| value.as_bool()) != Some(true);
+
+            (
+                "Butikerna idag",
+                hakan::plot::create_store_comparison(state, &report, by_ingredient, &style, false)
+                    .await?,
+            )
+        }
+        "kalender" => {
+            let year = match get_option(options, "år").and_then(|year| year.as_i64()) {
+                Some(year) => year as i32,
+                None => Utc::now().year(),
+            };
+
+            (
+                "Håkankalendern",
+                hakan::plot::create_calendar(state, year, &style, false).await?,
+            )
+        }
+        "inflation" => {
+            let comparison = hakan::cpi::compare(state).await?;
+            let plot = hakan::plot::create_inflation(state, &comparison, &style, false).await?;
+
+            let difference = comparison.difference();
+            let verdict = if difference > 0.0 {
+                format!("📈 Håkan har ökat `{difference:0.1}` procentenheter mer än inflationen.")
+            } else {
+                format!(
+                    "📉 Håkan har ökat `{:0.1}` procentenheter mindre än inflationen.",
+                    -difference
+                )
+            };
+
+            let period = match comparison.end() {
+                Some(end) => format!(
+                    "\n-# Jämfört till och med {}, den senaste månaden med KPI.",
+                    (end - TimeDelta::days(1)).format("%Y-%m")
+                ),
+                None => String::new(),
+            };
+
+            let embed = CreateEmbed::new()
+                .color(DARK_GREEN)
+                .title("Håkankursen mot inflationen")
+                .description(format!(
+                    "Håkan: `{:+0.1}%`\nKPI livsmedel: `{:+0.1}%`\n{verdict}{period}",
+                    comparison.hakan_change(),
+                    comparison.cpi_change()
+                ));
+
+            let response = with_plot(EditInteractionResponse::new(), embed, &plot, &style);
+
+            interaction.edit_response(&ctx.http, response).await?;
+
+            return Ok(());
+        }
         "uppdatera" => {
             let _ = hakan::update::send(&ctx.http, state).await?;
             let response = EditInteractionResponse::new().content(format!(
@@ -70,14 +261,34 @@ pub async fn run(
         _ => bail!("unknown subcommand"),
     };
 
-    let embed = CreateEmbed::new()
-        .color(DARK_GREEN)
-        .title(title)
-        .image(plot_url);
-
-    let response = EditInteractionResponse::new().add_embed(embed);
+    let embed = CreateEmbed::new().color(DARK_GREEN).title(title);
+    let response = with_plot(EditInteractionResponse::new(), embed, &plot, &style);
 
     interaction.edit_response(&ctx.http, response).await?;
 
     Ok(())
 }
+
+/// Shows the plot in the embed, or links to it if Discord can't display the format.
+/// Plots that weren't uploaded are attached to the response.
+fn with_plot(
+    response: EditInteractionResponse,
+    embed: CreateEmbed,
+    plot: &Plot,
+    style: &PlotStyle,
+) -> EditInteractionResponse {
+    let embed = match (style.format, &plot.url) {
+        (ImageFormat::Png, _) => embed.image(plot.embed_url()),
+        (ImageFormat::Svg, Some(url)) => embed
+            .field("Graf", format!("[Öppna som SVG]({url})"), false)
+            .url(url),
+        (ImageFormat::Svg, None) => embed.field("Graf", "Bifogad som SVG.", false),
+    };
+
+    let response = response.add_embed(embed);
+
+    match plot.attachment() {
+        Some(attachment) => response.new_attachment(attachment),
+        None => response,
+    }
+}
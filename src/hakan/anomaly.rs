@@ -0,0 +1,115 @@
+//! Catches prices that are far off from a product's recent history, which
+//! usually means a scraper misparsed something.
+
+use anyhow::Result;
+use chrono::{TimeDelta, Utc};
+use itertools::Itertools;
+use tracing::warn;
+
+use crate::AppState;
+
+use super::{Report, Store};
+
+/// How far back to look when building the price history.
+const HISTORY_DAYS: i64 = 30;
+/// Fewer points than this and we don't trust the history enough to judge.
+const MIN_HISTORY: usize = 5;
+/// Robust z-scores above this are considered outliers.
+const MAX_Z_SCORE: f64 = 3.5;
+/// Used instead when the price hasn't moved at all, since the MAD is zero then.
+const MAX_RELATIVE_CHANGE: f64 = 0.5;
+
+/// Compares every product's comparative price in `report` to the history for the same
+/// ingredient and store and puts the outliers in [`Report::anomalies`], along with
+/// their typical price.
+pub async fn detect(report: &mut Report, state: &AppState) -> Result<()> {
+    let since = Utc::now().naive_utc() - TimeDelta::days(HISTORY_DAYS);
+
+    let history = sqlx::query!(
+        r#"
+SELECT
+    products.ingredient_id,
+    products.store,
+    products.comparative_price
+FROM products
+JOIN reports
+    ON reports.id = products.report_id
+WHERE reports.created_at >= $1
+  AND NOT products.anomaly"#,
+        since
+    )
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .filter_map(|record| {
+        let store = Store::from_id(&record.store)?;
+        Some(((store, record.ingredient_id), record.comparative_price))
+    })
+    .into_group_map();
+
+    report.anomalies = report
+        .stores
+        .iter()
+        .flat_map(|(store, products)| {
+            products
+                .iter()
+                .map(move |(ingredient_id, product)| ((*store, *ingredient_id), product))
+        })
+        .filter_map(|(key, product)| {
+            let typical_price = typical_price(history.get(&key)?)?;
+
+            if !is_outlier(product.comparative_price, typical_price, &history[&key]) {
+                return None;
+            }
+
+            warn!(
+                store = key.0.id(),
+                product = product.name,
+                price = product.comparative_price,
+                typical_price,
+                "quarantined anomalous price"
+            );
+
+            Some((key, typical_price))
+        })
+        .collect();
+
+    Ok(())
+}
+
+/// The median of `history`, if the history is long enough to be trusted.
+fn typical_price(history: &[f64]) -> Option<f64> {
+    (history.len() >= MIN_HISTORY).then(|| median(history))
+}
+
+fn is_outlier(price: f64, median: f64, history: &[f64]) -> bool {
+    let deviations = history
+        .iter()
+        .map(|value| (value - median).abs())
+        .collect_vec();
+    let mad = self::median(&deviations);
+
+    if mad == 0.0 {
+        return ((price - median) / median).abs() > MAX_RELATIVE_CHANGE;
+    }
+
+    // 0.6745 scales the MAD to be comparable with a standard deviation
+    let z_score = 0.6745 * (price - median) / mad;
+
+    z_score.abs() > MAX_Z_SCORE
+}
+
+fn median(values: &[f64]) -> f64 {
+    let sorted = values
+        .iter()
+        .copied()
+        .sorted_by(f64::total_cmp)
+        .collect_vec();
+    let mid = sorted.len() / 2;
+
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
@@ -3,7 +3,7 @@ use serde::Deserialize;
 
 use crate::AppState;
 
-use super::Ingredient;
+use super::{CategoryCandidate, Ingredient, ProductSource};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -170,6 +170,38 @@ struct CategoryInfo {
     seo_description: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CategoryNode {
+    title: String,
+    url: String,
+    #[serde(default)]
+    children: Vec<CategoryNode>,
+}
+
+impl CategoryNode {
+    /// Collects every category below this one whose title contains `keyword`,
+    /// together with the titles of its ancestors.
+    fn find<'a>(
+        &'a self,
+        keyword: &str,
+        path: &mut Vec<&'a str>,
+        out: &mut Vec<(String, &'a Self)>,
+    ) {
+        path.push(&self.title);
+
+        if self.title.to_lowercase().contains(keyword) {
+            out.push((path.join(" > "), self));
+        }
+
+        for child in &self.children {
+            child.find(keyword, path, out);
+        }
+
+        path.pop();
+    }
+}
+
 async fn get_products_raw(
     category: &str,
     count: u32,
@@ -191,6 +223,28 @@ async fn get_products_raw(
     Ok(res.results)
 }
 
+async fn search_products_raw(
+    query: &str,
+    count: u32,
+    base_url: &str,
+    http: &reqwest::Client,
+) -> Result<Vec<Product>> {
+    let url = format!("https://{base_url}/search");
+
+    let text = http
+        .get(url)
+        .query(&[("q", query), ("page", "0"), ("size", &count.to_string())])
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let res: Response = serde_json::from_str(&text)?;
+
+    Ok(res.results)
+}
+
 async fn get_products(
     amount: f64,
     category: &str,
@@ -200,43 +254,131 @@ async fn get_products(
     let result = get_products_raw(category, 30, base_url, &state.http)
         .await?
         .into_iter()
-        .map(move |product| {
-            let comparative_price: f64 = product
-                .compare_price
-                .split_once(" ")
-                .map(|(a, _)| a)
-                .unwrap_or(&product.compare_price)
-                .replace(',', ".")
-                .parse()
-                .context("failed to parse compare price")?;
-
-            let comparative_price_text = format!("kr/{}", product.compare_price_unit);
-
-            let url = format!(
-                "https://{base_url}/produkt/{}-{}",
-                product
-                    .name
-                    .replace('&', "och")
-                    .replace('%', "procent")
-                    .replace('ö', "o")
-                    .replace(' ', ""),
-                product.code
-            );
-
-            Ok(super::Product {
-                url,
-                name: product.name,
-                manufacturer_name: product.manufacturer,
-                comparative_price,
-                comparative_price_text,
-                price: comparative_price * amount,
-            })
-        })
+        .map(|product| product.into_product(amount, base_url, ProductSource::Category))
         .collect::<Result<Vec<_>>>()?;
 
     Ok(result.into_iter())
 }
 
+async fn search_products(
+    ingredient: &Ingredient,
+    base_url: &str,
+    state: &AppState,
+) -> Result<impl Iterator<Item = super::Product>> {
+    let mut result = Vec::new();
+
+    for term in ingredient.search_terms() {
+        for product in search_products_raw(term, 30, base_url, &state.http).await? {
+            result.push(product.into_product(
+                ingredient.amount,
+                base_url,
+                ProductSource::Search,
+            )?);
+        }
+    }
+
+    Ok(result.into_iter())
+}
+
+impl Product {
+    fn into_product(
+        self,
+        amount: f64,
+        base_url: &str,
+        source: ProductSource,
+    ) -> Result<super::Product> {
+        let comparative_price: f64 = self
+            .compare_price
+            .split_once(" ")
+            .map(|(a, _)| a)
+            .unwrap_or(&self.compare_price)
+            .replace(',', ".")
+            .parse()
+            .context("failed to parse compare price")?;
+
+        let comparative_price_text = format!("kr/{}", self.compare_price_unit);
+
+        let url = format!(
+            "https://{base_url}/produkt/{}-{}",
+            self.name
+                .replace('&', "och")
+                .replace('%', "procent")
+                .replace('ö', "o")
+                .replace(' ', ""),
+            self.code
+        );
+
+        Ok(super::Product {
+            url,
+            name: self.name,
+            manufacturer_name: self.manufacturer,
+            comparative_price,
+            comparative_price_text,
+            price: comparative_price * amount,
+            shelf_price: Some(self.price_value),
+            package_size: Some(self.display_volume).filter(|volume| !volume.is_empty()),
+            source,
+        })
+    }
+}
+
+async fn discover_categories(
+    keyword: &str,
+    base_url: &str,
+    state: &AppState,
+) -> Result<Vec<CategoryCandidate>> {
+    let url = format!("https://{base_url}/leftMenu/categorytree");
+
+    let root: CategoryNode = state
+        .http
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let mut matches = Vec::new();
+    for child in &root.children {
+        child.find(keyword, &mut Vec::new(), &mut matches);
+    }
+
+    let mut candidates = Vec::new();
+
+    for (name, node) in matches.into_iter().take(super::DISCOVERY_MAX_CANDIDATES) {
+        let id = node.url.trim_matches('/').to_string();
+
+        let samples = get_products_raw(
+            &id,
+            super::DISCOVERY_SAMPLE_COUNT as u32,
+            base_url,
+            &state.http,
+        )
+        .await?
+        .into_iter()
+        .map(|product| format!("{} {}", product.manufacturer, product.name))
+        .collect();
+
+        candidates.push(CategoryCandidate { id, name, samples });
+    }
+
+    Ok(candidates)
+}
+
+pub async fn discover_willys_categories(
+    keyword: &str,
+    state: &AppState,
+) -> Result<Vec<CategoryCandidate>> {
+    discover_categories(keyword, "www.willys.se", state).await
+}
+
+pub async fn discover_hemkop_categories(
+    keyword: &str,
+    state: &AppState,
+) -> Result<Vec<CategoryCandidate>> {
+    discover_categories(keyword, "www.hemkop.se", state).await
+}
+
 pub async fn get_willys_products(
     ingredient: &Ingredient,
     state: &AppState,
@@ -262,3 +404,17 @@ pub async fn get_hemkop_products(
     )
     .await
 }
+
+pub async fn search_willys_products(
+    ingredient: &Ingredient,
+    state: &AppState,
+) -> Result<impl Iterator<Item = super::Product>> {
+    search_products(ingredient, "www.willys.se", state).await
+}
+
+pub async fn search_hemkop_products(
+    ingredient: &Ingredient,
+    state: &AppState,
+) -> Result<impl Iterator<Item = super::Product>> {
+    search_products(ingredient, "www.hemkop.se", state).await
+}
@@ -0,0 +1,199 @@
+//! Imports hand-collected prices from before the bot existed.
+//!
+//! The CSV has the columns `date, store, ingredient, product name, comparative price, unit`,
+//! for example `2025-03-01,ICA,Smör,Svenskt smör,109.90,kr/kg`. The first row may be a header.
+//! Every date becomes a backdated report marked as imported. Importing the same date again
+//! replaces the previously imported report, so re-running an import is safe. Dates that
+//! already have a scraped report are skipped.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{NaiveDate, NaiveTime};
+use itertools::Itertools;
+
+use crate::AppState;
+
+use super::{db, Ingredient, Store};
+
+/// Imported reports are placed at the time the daily update runs.
+const REPORT_TIME: NaiveTime = match NaiveTime::from_hms_opt(7, 0, 0) {
+    Some(time) => time,
+    None => panic!("invalid report time"),
+};
+
+struct Row<'a> {
+    store: Store,
+    ingredient: &'a Ingredient,
+    name: String,
+    comparative_price: f64,
+    comparative_price_text: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub reports: usize,
+    pub products: usize,
+    /// Reports that already existed and were replaced.
+    pub replaced: usize,
+    /// Dates skipped because they already have a scraped report.
+    pub skipped: usize,
+}
+
+/// Validates and imports the CSV at `path`. Nothing is written if `dry_run` is set.
+pub async fn import(path: impl AsRef<Path>, dry_run: bool, state: &AppState) -> Result<Summary> {
+    let text = tokio::fs::read_to_string(path).await?;
+    let ingredients = db::ingredients(state).await?;
+
+    let delimiter = if text.lines().next().unwrap_or_default().contains(';') {
+        ';'
+    } else {
+        ','
+    };
+
+    let mut errors = Vec::new();
+    let mut days: BTreeMap<NaiveDate, Vec<Row>> = BTreeMap::new();
+
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let columns = split_line(line, delimiter);
+
+        if i == 0 && NaiveDate::parse_from_str(&columns[0], "%Y-%m-%d").is_err() {
+            // header
+            continue;
+        }
+
+        match parse_row(&columns, &ingredients) {
+            Ok((date, row)) => days.entry(date).or_default().push(row),
+            Err(err) => errors.push(format!("line {}: {err:#}", i + 1)),
+        }
+    }
+
+    if !errors.is_empty() {
+        bail!("invalid rows:\n{}", errors.join("\n"));
+    }
+
+    let mut summary = Summary::default();
+    let mut tx = state.db.begin().await?;
+
+    for (date, rows) in &days {
+        let created_at = date.and_time(REPORT_TIME);
+
+        let replaced = sqlx::query!("DELETE FROM reports WHERE imported AND date = $1", date)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        let Some(report) = sqlx::query!(
+            "INSERT INTO reports (created_at, date, imported) VALUES ($1, $2, TRUE)
+            ON CONFLICT (date) DO NOTHING
+            RETURNING id",
+            created_at,
+            date
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            summary.skipped += 1;
+            continue;
+        };
+        let report_id = report.id;
+
+        for row in rows {
+            sqlx::query!(
+                "INSERT INTO products
+                (report_id, ingredient_id, name, manufacturer_name, comparative_price, comparative_price_text, url, store, price, ingredient_amount)
+                VALUES ($1, $2, $3, '', $4, $5, '', $6, $7, $8)",
+                report_id,
+                row.ingredient.id,
+                row.name,
+                row.comparative_price,
+                row.comparative_price_text,
+                row.store.id(),
+                row.comparative_price * row.ingredient.amount,
+                row.ingredient.amount
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        summary.reports += 1;
+        summary.products += rows.len();
+        summary.replaced += replaced as usize;
+    }
+
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+
+    Ok(summary)
+}
+
+fn parse_row<'a>(
+    columns: &[String],
+    ingredients: &'a [Ingredient],
+) -> Result<(NaiveDate, Row<'a>)> {
+    let [date, store, ingredient, name, price, unit] = columns else {
+        bail!("expected 6 columns, found {}", columns.len());
+    };
+
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .with_context(|| format!("invalid date {date}"))?;
+
+    let store = Store::ALL
+        .into_iter()
+        .find(|known| {
+            known.id().eq_ignore_ascii_case(store)
+                || known.to_string().to_lowercase() == store.to_lowercase()
+        })
+        .ok_or_else(|| anyhow!("unknown store {store}"))?;
+
+    let ingredient = ingredients
+        .iter()
+        .find(|known| known.name.to_lowercase() == ingredient.to_lowercase())
+        .ok_or_else(|| anyhow!("unknown ingredient {ingredient}"))?;
+
+    let comparative_price = price
+        .replace(',', ".")
+        .parse()
+        .with_context(|| format!("invalid price {price}"))?;
+
+    let row = Row {
+        store,
+        ingredient,
+        name: name.clone(),
+        comparative_price,
+        comparative_price_text: unit.clone(),
+    };
+
+    Ok((date, row))
+}
+
+/// Splits a CSV line, allowing quoted columns with escaped `""` quotes.
+fn split_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut columns = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                columns.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => columns.push(String::new()),
+            c => columns.last_mut().unwrap().push(c),
+        }
+    }
+
+    columns
+        .into_iter()
+        .map(|column| column.trim().to_string())
+        .collect_vec()
+}
@@ -0,0 +1,99 @@
+//! Keeps the storage bucket from filling up with plots.
+//!
+//! Every plot that's uploaded gets its own object, so old ones are deleted once they're
+//! older than the retention period, unless one of the latest daily updates still shows them.
+
+use std::collections::HashSet;
+
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use serenity::all::{GetMessages, Http};
+use tracing::{info, warn};
+
+use crate::AppState;
+
+/// Used if `HAKAN_PLOT_RETENTION_DAYS` isn't set.
+const DEFAULT_PLOT_RETENTION_DAYS: i64 = 30;
+/// How many messages back in the update channel to look for plots that are still shown.
+const RECENT_UPDATES: u8 = 100;
+
+#[derive(Debug, Default)]
+pub struct Freed {
+    pub objects: usize,
+    pub bytes: u64,
+    /// Plots that should have been deleted but couldn't be, they're retried next time.
+    pub failed: usize,
+}
+
+/// How many days to keep plots for.
+pub fn plot_retention_days() -> i64 {
+    std::env::var("HAKAN_PLOT_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(DEFAULT_PLOT_RETENTION_DAYS)
+        .max(1)
+}
+
+/// Deletes every plot older than [`plot_retention_days`] that isn't in a recent daily update.
+pub async fn delete_old_plots(http: &Http, state: &AppState) -> Result<Freed> {
+    let Some(storage) = &state.storage else {
+        return Ok(Freed::default());
+    };
+
+    let cutoff = Utc::now() - TimeDelta::days(plot_retention_days());
+    let referenced = referenced_plots(http).await?;
+
+    let mut freed = Freed::default();
+
+    for object in storage.list("plots").await? {
+        // folders don't have a creation date
+        let Some(created_at) = object
+            .created_at
+            .as_deref()
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        else {
+            continue;
+        };
+
+        if created_at >= cutoff || referenced.contains(&object.name) {
+            continue;
+        }
+
+        // one failed delete shouldn't keep the rest from being cleaned up
+        if let Err(err) = storage.delete(format!("plots/{}", object.name)).await {
+            warn!(name = object.name, "failed to delete plot: {err:#}");
+            freed.failed += 1;
+            continue;
+        }
+
+        freed.objects += 1;
+        freed.bytes += object.metadata.map(|metadata| metadata.size).unwrap_or(0);
+    }
+
+    info!(
+        objects = freed.objects,
+        bytes = freed.bytes,
+        failed = freed.failed,
+        %cutoff,
+        "deleted old plots"
+    );
+
+    Ok(freed)
+}
+
+/// The file names of the plots in the latest messages of the update channel.
+async fn referenced_plots(http: &Http) -> Result<HashSet<String>> {
+    let messages = super::update::CHANNEL
+        .messages(http, GetMessages::new().limit(RECENT_UPDATES))
+        .await?;
+
+    let names = messages
+        .iter()
+        .flat_map(|message| &message.embeds)
+        .filter_map(|embed| embed.image.as_ref())
+        .filter_map(|image| image.url.rsplit('/').next())
+        .map(|name| name.to_string())
+        .collect();
+
+    Ok(names)
+}
@@ -1,10 +1,11 @@
 use anyhow::Result;
 use convert_case::{Case, Casing};
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 use crate::AppState;
 
-use super::Ingredient;
+use super::{CategoryCandidate, Ingredient, ProductSource};
 
 #[derive(Debug, Deserialize)]
 struct Response {
@@ -181,6 +182,13 @@ struct Request {
     results_options: ResultsOptions,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchRequest {
+    query: String,
+    results_options: ResultsOptions,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct RequestAttribute {
@@ -248,6 +256,76 @@ async fn get_products_raw(
     Ok(res.results.items)
 }
 
+async fn search_products_raw(
+    http: &reqwest::Client,
+    query: &str,
+    count: u32,
+) -> Result<Vec<Product>> {
+    const URL: &str = "https://external.api.coop.se/personalization/search/products?api-version=v1&store=251300&groups=CUSTOMER_PRIVATE";
+
+    let req = SearchRequest {
+        query: query.to_string(),
+        results_options: ResultsOptions {
+            skip: 0,
+            take: count,
+            sort_by: Vec::new(),
+        },
+    };
+
+    let text = http
+        .post(URL)
+        .json(&req)
+        .header("Ocp-Apim-Subscription-Key", API_KEY)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let res: Response = serde_json::from_str(&text)?;
+
+    Ok(res.results.items)
+}
+
+pub async fn discover_categories(
+    keyword: &str,
+    state: &AppState,
+) -> Result<Vec<CategoryCandidate>> {
+    let products = search_products_raw(&state.http, keyword, 50).await?;
+
+    let candidates = products
+        .iter()
+        .filter_map(|product| {
+            let category = product.nav_categories.first()?;
+            Some((category, product))
+        })
+        .into_group_map_by(|(category, _)| category.code.clone())
+        .into_iter()
+        .map(|(code, products)| {
+            let name = products[0].0.path();
+            let samples = products
+                .iter()
+                .take(super::DISCOVERY_SAMPLE_COUNT)
+                .map(|(_, product)| format!("{} {}", product.manufacturer_name, product.name))
+                .collect();
+
+            (
+                products.len(),
+                CategoryCandidate {
+                    id: code,
+                    name,
+                    samples,
+                },
+            )
+        })
+        .sorted_by(|(a, _), (b, _)| b.cmp(a))
+        .map(|(_, candidate)| candidate)
+        .take(super::DISCOVERY_MAX_CANDIDATES)
+        .collect();
+
+    Ok(candidates)
+}
+
 pub async fn get_products(
     ingredient: &Ingredient,
     state: &AppState,
@@ -265,24 +343,60 @@ pub async fn get_products(
     )
     .await?
     .into_iter()
-    .map(move |product| {
-        let url = product.url();
+    .filter_map(move |product| product.into_product(amount, ProductSource::Category)))
+}
 
-        super::Product {
-            url,
-            name: product.name,
-            manufacturer_name: product.manufacturer_name,
-            comparative_price: product.comparative_price,
-            comparative_price_text: product.comparative_price_text,
-            price: product.comparative_price * amount,
+pub async fn search_products(
+    ingredient: &Ingredient,
+    state: &AppState,
+) -> Result<impl Iterator<Item = super::Product>> {
+    let mut products = Vec::new();
+
+    for term in ingredient.search_terms() {
+        products.extend(search_products_raw(&state.http, term, 20).await?);
+    }
+
+    let amount = ingredient.amount;
+
+    Ok(products
+        .into_iter()
+        .filter_map(move |product| product.into_product(amount, ProductSource::Search)))
+}
+
+impl NavCategory {
+    fn path(&self) -> String {
+        let mut names = vec![self.name.as_str()];
+        let mut current = self;
+        while let Some(parent) = current.super_categories.first() {
+            names.push(&parent.name);
+            current = parent;
         }
-    }))
+
+        names.into_iter().rev().join(" > ")
+    }
 }
 
 impl Product {
-    fn url(&self) -> String {
+    /// Returns `None` for products without a category, since there's no page to link to.
+    fn into_product(self, amount: f64, source: ProductSource) -> Option<super::Product> {
+        let url = self.url()?;
+
+        Some(super::Product {
+            url,
+            name: self.name,
+            manufacturer_name: self.manufacturer_name,
+            comparative_price: self.comparative_price,
+            comparative_price_text: self.comparative_price_text,
+            price: self.comparative_price * amount,
+            shelf_price: Some(self.sales_price),
+            package_size: Some(self.package_size_information),
+            source,
+        })
+    }
+
+    fn url(&self) -> Option<String> {
         let mut categories = Vec::new();
-        let mut current = self.nav_categories.iter().next().unwrap();
+        let mut current = self.nav_categories.first()?;
         loop {
             categories.push(&current.name);
             match current.super_categories.iter().next() {
@@ -301,6 +415,6 @@ impl Product {
         url.push('-');
         url.push_str(&self.id.to_string());
 
-        url
+        Some(url)
     }
 }
@@ -0,0 +1,161 @@
+//! The food consumer price index (KPI for livsmedel) from SCB, used to compare
+//! the Håkan index against general food inflation.
+
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+
+use crate::AppState;
+
+use super::db::{self, Range};
+
+/// Parses a CSV exported from SCB's statistical database and stores every month in it.
+///
+/// Months are written as `2025M04` and values may use a decimal comma.
+/// Rows that don't start with a month, like headers, are skipped.
+/// Returns the number of months imported.
+pub async fn import(path: impl AsRef<Path>, state: &AppState) -> Result<usize> {
+    let text = tokio::fs::read_to_string(path).await?;
+
+    let months = text
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| parse_line(line).map(|res| (i, res)))
+        .map(|(i, res)| res.with_context(|| format!("invalid row on line {}", i + 1)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut tx = state.db.begin().await?;
+
+    for (month, value) in &months {
+        sqlx::query!(
+            "INSERT INTO cpi (month, value) VALUES ($1, $2)
+            ON CONFLICT (month) DO UPDATE SET value = EXCLUDED.value",
+            month,
+            value
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(months.len())
+}
+
+/// Returns `None` if the line isn't a data row.
+fn parse_line(line: &str) -> Option<Result<(NaiveDate, f64)>> {
+    let mut columns = line
+        .split([',', ';', '\t'])
+        .map(|column| column.trim().trim_matches('"'));
+
+    let (year, month) = columns.next()?.split_once('M')?;
+    let year = year.parse().ok()?;
+
+    Some(parse_row(year, month, columns.next_back()))
+}
+
+fn parse_row(year: i32, month: &str, value: Option<&str>) -> Result<(NaiveDate, f64)> {
+    let month = month.parse().context("invalid month")?;
+    let date = NaiveDate::from_ymd_opt(year, month, 1).ok_or_else(|| anyhow!("invalid month"))?;
+
+    let value = value
+        .context("missing value")?
+        .replace(',', ".")
+        .parse()
+        .context("invalid value")?;
+
+    Ok((date, value))
+}
+
+/// The Håkan index and the food CPI, both rebased to 100 at the first report.
+pub struct Comparison {
+    pub hakan: Vec<(DateTime<Utc>, f64)>,
+    pub cpi: Vec<(DateTime<Utc>, f64)>,
+}
+
+impl Comparison {
+    /// How much more the Håkan index has risen than the CPI, in percentage points.
+    pub fn difference(&self) -> f64 {
+        self.hakan_change() - self.cpi_change()
+    }
+
+    /// Only counts the Håkan index up to the end of the last month with a CPI value,
+    /// so that both changes cover the same period.
+    pub fn hakan_change(&self) -> f64 {
+        let end = self.end();
+        let len = self
+            .hakan
+            .partition_point(|(date, _)| end.is_none_or(|end| *date < end));
+
+        change(&self.hakan[..len])
+    }
+
+    pub fn cpi_change(&self) -> f64 {
+        change(&self.cpi)
+    }
+
+    /// The end of the last month with a CPI value.
+    pub fn end(&self) -> Option<DateTime<Utc>> {
+        self.cpi
+            .last()
+            .and_then(|(month, _)| month.checked_add_months(Months::new(1)))
+    }
+}
+
+fn change(series: &[(DateTime<Utc>, f64)]) -> f64 {
+    series
+        .last()
+        .map(|(_, value)| value - 100.0)
+        .unwrap_or_default()
+}
+
+pub async fn compare(state: &AppState) -> Result<Comparison> {
+    let range = Range::default();
+    let resolution = db::resolution(&range, state).await?;
+    let reports = db::reports(&range, resolution, state).await?;
+
+    let first = reports.first().context("there are no reports yet")?;
+    let first_month = first
+        .created_at
+        .date()
+        .with_day(1)
+        .expect("every month has a first day");
+
+    let cpi = sqlx::query!(
+        "SELECT month, value FROM cpi WHERE month >= $1 ORDER BY month ASC",
+        first_month
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let cpi_base = cpi
+        .first()
+        .filter(|record| record.month == first_month)
+        .ok_or_else(|| anyhow!("the CPI for {first_month} hasn't been imported"))?
+        .value;
+
+    let hakan_base = first.price;
+
+    let hakan = reports
+        .iter()
+        .map(|report| {
+            (
+                report.created_at.and_utc(),
+                report.price / hakan_base * 100.0,
+            )
+        })
+        .collect();
+
+    let cpi = cpi
+        .iter()
+        .map(|record| {
+            (
+                record.month.and_time(Default::default()).and_utc(),
+                record.value / cpi_base * 100.0,
+            )
+        })
+        .collect();
+
+    Ok(Comparison { hakan, cpi })
+}
@@ -1,27 +1,156 @@
-use anyhow::Result;
-use chrono::NaiveDateTime;
+use anyhow::{bail, Context, Result};
+use chrono::{Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc};
 
 use crate::AppState;
 
-use super::Product;
+use super::{Ingredient, Product, ProductSource};
+
+/// A time range to limit history queries to. `None` means unbounded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Range {
+    pub from: Option<NaiveDateTime>,
+    /// Exclusive.
+    pub to: Option<NaiveDateTime>,
+}
+
+impl Range {
+    pub fn last_week() -> Self {
+        Self::since(Utc::now().naive_utc() - TimeDelta::weeks(1))
+    }
+
+    pub fn last_month() -> Self {
+        Self::since(Utc::now().naive_utc() - Months::new(1))
+    }
+
+    pub fn last_days(days: i64) -> Self {
+        Self::since(Utc::now().naive_utc() - TimeDelta::days(days))
+    }
+
+    pub fn last_year() -> Self {
+        Self::since(Utc::now().naive_utc() - Months::new(12))
+    }
+
+    /// Parses `YYYY-MM-DD` dates into a range that includes both days.
+    pub fn from_dates(from: Option<&str>, to: Option<&str>) -> Result<Self> {
+        let from = from.map(parse_date).transpose()?;
+        let to = to
+            .map(parse_date)
+            .transpose()?
+            .map(|to| to + TimeDelta::days(1));
+
+        Ok(Self { from, to })
+    }
+
+    fn since(from: NaiveDateTime) -> Self {
+        Self {
+            from: Some(from),
+            to: None,
+        }
+    }
+}
+
+fn parse_date(date: &str) -> Result<NaiveDateTime> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .with_context(|| format!("invalid date {date}, expected YYYY-MM-DD"))?;
+
+    Ok(date.and_time(NaiveTime::MIN))
+}
+
+/// How long each data point in a history query covers.
+///
+/// Each bucket is the average of the reports inside it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Day,
+    Week,
+    Month,
+}
+
+impl Resolution {
+    /// Picks a resolution that keeps the number of points in a plot readable.
+    pub fn for_span(span: TimeDelta) -> Self {
+        if span <= TimeDelta::days(90) {
+            Resolution::Day
+        } else if span <= TimeDelta::days(2 * 365) {
+            Resolution::Week
+        } else {
+            Resolution::Month
+        }
+    }
+
+    /// The field name for postgres' `date_trunc`.
+    fn unit(&self) -> &'static str {
+        match self {
+            Resolution::Day => "day",
+            Resolution::Week => "week",
+            Resolution::Month => "month",
+        }
+    }
+}
+
+/// Picks a resolution based on how much history `range` actually covers.
+pub async fn resolution(range: &Range, state: &AppState) -> Result<Resolution> {
+    let from = match range.from {
+        Some(from) => from,
+        None => {
+            let first = sqlx::query_scalar!("SELECT MIN(created_at) FROM reports")
+                .fetch_one(&state.db)
+                .await?;
+
+            match first {
+                Some(first) => first,
+                None => return Ok(Resolution::Day),
+            }
+        }
+    };
+
+    let to = range.to.unwrap_or_else(|| Utc::now().naive_utc());
+
+    Ok(Resolution::for_span(to - from))
+}
 
 pub struct Report {
     pub created_at: NaiveDateTime,
     pub price: f64,
 }
 
-pub async fn reports(state: &AppState) -> Result<Vec<Report>> {
+pub async fn reports(
+    range: &Range,
+    resolution: Resolution,
+    state: &AppState,
+) -> Result<Vec<Report>> {
     let records = sqlx::query_as!(
         Report,
         r#"
+WITH totals AS (
+    SELECT
+        reports.created_at,
+        SUM(p.price) AS price
+    FROM reports
+    JOIN cheapest_products p
+        ON p.report_id = reports.id
+    WHERE ($1::timestamp IS NULL OR reports.created_at >= $1)
+      AND ($2::timestamp IS NULL OR reports.created_at < $2)
+    GROUP BY reports.created_at
+    UNION ALL
+    SELECT
+        reports.created_at,
+        daily_totals.price
+    FROM reports
+    JOIN daily_totals
+        ON daily_totals.report_id = reports.id
+    WHERE ($1::timestamp IS NULL OR reports.created_at >= $1)
+      AND ($2::timestamp IS NULL OR reports.created_at < $2)
+)
 SELECT
-    reports.created_at,
-    SUM(p.price) AS "price!"
-FROM reports
-JOIN cheapest_products p
-    ON p.report_id = reports.id
-GROUP BY reports.created_at
-ORDER BY created_at ASC"#,
+    date_trunc($3, created_at) AS "created_at!",
+    AVG(price) AS "price!"
+FROM totals
+GROUP BY 1
+ORDER BY 1 ASC"#,
+        range.from,
+        range.to,
+        resolution.unit()
     )
     .fetch_all(&state.db)
     .await?;
@@ -29,34 +158,102 @@ ORDER BY created_at ASC"#,
     Ok(records)
 }
 
+/// The highest and lowest total price of all time, with the time of each report.
+pub struct Extremes {
+    pub high: (NaiveDateTime, f64),
+    pub low: (NaiveDateTime, f64),
+}
+
+/// The all-time [`Extremes`] of the total price, including reports that have been
+/// rolled up by [`super::retention`]. Returns `None` if there are no reports.
+pub async fn total_extremes(state: &AppState) -> Result<Option<Extremes>> {
+    let record = sqlx::query!(
+        r#"
+WITH totals AS (
+    SELECT
+        reports.created_at,
+        SUM(p.price) AS price
+    FROM reports
+    JOIN cheapest_products p
+        ON p.report_id = reports.id
+    GROUP BY reports.created_at
+    UNION ALL
+    SELECT
+        reports.created_at,
+        daily_totals.price
+    FROM reports
+    JOIN daily_totals
+        ON daily_totals.report_id = reports.id
+)
+SELECT
+    (ARRAY_AGG(created_at ORDER BY price DESC))[1] AS high_at,
+    MAX(price) AS high,
+    (ARRAY_AGG(created_at ORDER BY price ASC))[1] AS low_at,
+    MIN(price) AS low
+FROM totals"#
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    let (Some(high_at), Some(high), Some(low_at), Some(low)) =
+        (record.high_at, record.high, record.low_at, record.low)
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(Extremes {
+        high: (high_at, high),
+        low: (low_at, low),
+    }))
+}
+
 pub struct ReportWithStore {
     pub created_at: NaiveDateTime,
     pub price: f64,
     pub store: String,
 }
 
-pub async fn reports_by_store(state: &AppState) -> Result<Vec<ReportWithStore>> {
+pub async fn reports_by_store(
+    range: &Range,
+    resolution: Resolution,
+    state: &AppState,
+) -> Result<Vec<ReportWithStore>> {
     let records = sqlx::query_as!(
         ReportWithStore,
         r#"
-WITH p AS (
+WITH totals AS (
     SELECT
-      products.store,
-      products.report_id,
-      (products.comparative_price * ingredients.amount) AS price
-    FROM products
-    JOIN ingredients
-      ON ingredients.id = products.ingredient_id
+        reports.created_at,
+        p.store,
+        SUM(p.price) AS price
+    FROM reports
+    JOIN products p
+        ON p.report_id = reports.id
+    WHERE ($1::timestamp IS NULL OR reports.created_at >= $1)
+      AND ($2::timestamp IS NULL OR reports.created_at < $2)
+      AND NOT p.anomaly
+    GROUP BY p.store, reports.created_at
+    UNION ALL
+    SELECT
+        reports.created_at,
+        daily_store_totals.store,
+        daily_store_totals.price
+    FROM reports
+    JOIN daily_store_totals
+        ON daily_store_totals.report_id = reports.id
+    WHERE ($1::timestamp IS NULL OR reports.created_at >= $1)
+      AND ($2::timestamp IS NULL OR reports.created_at < $2)
 )
 SELECT
-    reports.created_at,
-    p.store,
-    SUM(p.price) AS "price!"
-FROM reports
-JOIN p 
-    ON p.report_id = reports.id
-GROUP BY p.store, reports.created_at
-ORDER BY created_at ASC"#,
+    date_trunc($3, created_at) AS "created_at!",
+    store AS "store!",
+    AVG(price) AS "price!"
+FROM totals
+GROUP BY 1, store
+ORDER BY 1 ASC"#,
+        range.from,
+        range.to,
+        resolution.unit()
     )
     .fetch_all(&state.db)
     .await?;
@@ -70,26 +267,55 @@ pub struct ReportWithIngredient {
     pub ingredient_name: String,
 }
 
-pub async fn reports_by_ingredient(state: &AppState) -> Result<Vec<ReportWithIngredient>> {
+pub async fn reports_by_ingredient(
+    range: &Range,
+    resolution: Resolution,
+    state: &AppState,
+) -> Result<Vec<ReportWithIngredient>> {
     let records = sqlx::query_as!(
         ReportWithIngredient,
         r#"
+WITH cheapest AS (
+    SELECT
+        ingredients.name AS ingredient_name,
+        reports.created_at,
+        MIN(products.price) AS price
+    FROM ingredients
+    JOIN products
+        ON products.ingredient_id = ingredients.id
+    JOIN reports
+        ON products.report_id = reports.id
+    WHERE ($1::timestamp IS NULL OR reports.created_at >= $1)
+      AND ($2::timestamp IS NULL OR reports.created_at < $2)
+      AND NOT products.anomaly
+    GROUP BY
+        ingredient_id,
+        report_id,
+        ingredients.name,
+        reports.created_at
+    UNION ALL
+    SELECT
+        ingredients.name,
+        reports.created_at,
+        daily_ingredient_prices.price
+    FROM ingredients
+    JOIN daily_ingredient_prices
+        ON daily_ingredient_prices.ingredient_id = ingredients.id
+    JOIN reports
+        ON daily_ingredient_prices.report_id = reports.id
+    WHERE ($1::timestamp IS NULL OR reports.created_at >= $1)
+      AND ($2::timestamp IS NULL OR reports.created_at < $2)
+)
 SELECT
-  ingredients.name AS ingredient_name,
-  reports.created_at,
-  MIN(products.comparative_price) * amount AS "price!"
-FROM ingredients
-LEFT JOIN products
-	ON products.ingredient_id = ingredients.id
-LEFT JOIN reports
-	ON products.report_id = reports.id
-GROUP BY 
-    ingredient_id, 
-    report_id, 
-    ingredients.name, 
-    reports.created_at, 
-    ingredients.amount
-ORDER BY created_at ASC"#,
+    ingredient_name AS "ingredient_name!",
+    date_trunc($3, created_at) AS "created_at!",
+    AVG(price) AS "price!"
+FROM cheapest
+GROUP BY ingredient_name, 2
+ORDER BY 2 ASC"#,
+        range.from,
+        range.to,
+        resolution.unit()
     )
     .fetch_all(&state.db)
     .await?;
@@ -97,6 +323,131 @@ ORDER BY created_at ASC"#,
     Ok(records)
 }
 
+pub struct ProductRecord {
+    pub created_at: NaiveDateTime,
+    pub store: String,
+    pub ingredient_name: String,
+    pub name: String,
+    pub manufacturer_name: String,
+    pub comparative_price: f64,
+    pub comparative_price_text: String,
+    pub price: f64,
+    pub shelf_price: Option<f64>,
+    pub package_size: Option<String>,
+    pub url: String,
+    pub source: String,
+    pub anomaly: bool,
+}
+
+pub struct ReportWithIngredientAndStore {
+    pub created_at: NaiveDateTime,
+    pub price: f64,
+    pub ingredient_name: String,
+    pub store: String,
+}
+
+/// The price of each ingredient at each store, optionally for a single ingredient.
+pub async fn reports_by_ingredient_and_store(
+    range: &Range,
+    resolution: Resolution,
+    ingredient: Option<&str>,
+    state: &AppState,
+) -> Result<Vec<ReportWithIngredientAndStore>> {
+    let records = sqlx::query_as!(
+        ReportWithIngredientAndStore,
+        r#"
+WITH prices AS (
+    SELECT
+        ingredients.name AS ingredient_name,
+        products.store,
+        reports.created_at,
+        MIN(products.price) AS price
+    FROM ingredients
+    JOIN products
+        ON products.ingredient_id = ingredients.id
+    JOIN reports
+        ON products.report_id = reports.id
+    WHERE ($1::timestamp IS NULL OR reports.created_at >= $1)
+      AND ($2::timestamp IS NULL OR reports.created_at < $2)
+      AND NOT products.anomaly
+    GROUP BY
+        ingredients.name,
+        products.store,
+        reports.created_at
+    UNION ALL
+    SELECT
+        ingredients.name,
+        daily_store_ingredient_prices.store,
+        reports.created_at,
+        daily_store_ingredient_prices.price
+    FROM ingredients
+    JOIN daily_store_ingredient_prices
+        ON daily_store_ingredient_prices.ingredient_id = ingredients.id
+    JOIN reports
+        ON daily_store_ingredient_prices.report_id = reports.id
+    WHERE ($1::timestamp IS NULL OR reports.created_at >= $1)
+      AND ($2::timestamp IS NULL OR reports.created_at < $2)
+)
+SELECT
+    ingredient_name AS "ingredient_name!",
+    store AS "store!",
+    date_trunc($3, created_at) AS "created_at!",
+    AVG(price) AS "price!"
+FROM prices
+WHERE ($4::text IS NULL OR ingredient_name = $4)
+GROUP BY ingredient_name, store, 3
+ORDER BY 3 ASC"#,
+        range.from,
+        range.to,
+        resolution.unit(),
+        ingredient
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(records)
+}
+
+/// Every stored product in `range`, not just the cheapest ones.
+///
+/// Products older than the retention period have been pruned, see [`super::retention`].
+pub async fn products(range: &Range, state: &AppState) -> Result<Vec<ProductRecord>> {
+    let records = sqlx::query_as!(
+        ProductRecord,
+        r#"
+SELECT
+    reports.created_at,
+    products.store,
+    ingredients.name AS ingredient_name,
+    products.name,
+    products.manufacturer_name,
+    products.comparative_price,
+    products.comparative_price_text,
+    products.price,
+    products.shelf_price,
+    products.package_size,
+    products.url,
+    products.source,
+    products.anomaly
+FROM products
+JOIN reports
+    ON reports.id = products.report_id
+JOIN ingredients
+    ON ingredients.id = products.ingredient_id
+WHERE ($1::timestamp IS NULL OR reports.created_at >= $1)
+  AND ($2::timestamp IS NULL OR reports.created_at < $2)
+ORDER BY reports.created_at ASC, products.store, ingredients.name"#,
+        range.from,
+        range.to
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(records)
+}
+
+/// The cheapest products of the latest report before today, so that re-running
+/// today's update still compares against yesterday.
 pub async fn last_products(state: &AppState) -> Result<Vec<(String, Product)>> {
     let records = sqlx::query!(
         r#"
@@ -107,14 +458,17 @@ SELECT
     p.comparative_price_text,
     p.url,
     p.price,
+    p.shelf_price,
+    p.package_size,
+    p.source,
     ingredients.name AS ingredient_name
 FROM reports
 LEFT JOIN cheapest_products p
 	ON p.report_id = reports.id
 LEFT JOIN ingredients
 	ON p.ingredient_id = ingredients.id
-WHERE reports.created_at = (
-    SELECT MAX(created_at) FROM reports
+WHERE reports.date = (
+    SELECT MAX(date) FROM reports WHERE date < CURRENT_DATE
 )"#,
     )
     .map(|record| {
@@ -127,6 +481,13 @@ WHERE reports.created_at = (
                 comparative_price_text: record.comparative_price_text.unwrap(),
                 url: record.url.unwrap(),
                 price: record.price.unwrap(),
+                shelf_price: record.shelf_price,
+                package_size: record.package_size,
+                source: record
+                    .source
+                    .as_deref()
+                    .and_then(ProductSource::from_id)
+                    .unwrap_or(ProductSource::Category),
             },
         )
     })
@@ -135,3 +496,337 @@ WHERE reports.created_at = (
 
     Ok(records)
 }
+
+pub struct SnapshotProduct {
+    pub ingredient_name: String,
+    pub store: String,
+    pub name: String,
+    pub manufacturer_name: String,
+    pub price: f64,
+    /// Whether this is the cheapest product for its ingredient.
+    pub cheapest: bool,
+}
+
+/// Every product of the latest report on or before `date`, along with the date of that report.
+///
+/// Returns `None` if there is no such report, and fails if its products have been
+/// rolled up by [`super::retention`].
+pub async fn snapshot(
+    date: NaiveDate,
+    state: &AppState,
+) -> Result<Option<(NaiveDate, Vec<SnapshotProduct>)>> {
+    let date = sqlx::query_scalar!("SELECT MAX(date) FROM reports WHERE date <= $1", date)
+        .fetch_one(&state.db)
+        .await?;
+
+    let Some(date) = date else {
+        return Ok(None);
+    };
+
+    if downsampled_until(state)
+        .await?
+        .is_some_and(|until| date <= until)
+    {
+        bail!("the products of the report from {date} have been pruned, only the daily totals are kept");
+    }
+
+    let records = sqlx::query_as!(
+        SnapshotProduct,
+        r#"
+SELECT
+    ingredients.name AS ingredient_name,
+    products.store,
+    products.name,
+    products.manufacturer_name,
+    products.price,
+    cheapest_products.id IS NOT NULL AS "cheapest!"
+FROM products
+JOIN reports
+    ON reports.id = products.report_id
+JOIN ingredients
+    ON ingredients.id = products.ingredient_id
+LEFT JOIN cheapest_products
+    ON cheapest_products.id = products.id
+WHERE reports.date = $1
+ORDER BY ingredients.name, products.store"#,
+        date
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Some((date, records)))
+}
+
+/// The date of the newest report whose products have been rolled up by [`super::retention`].
+pub async fn downsampled_until(state: &AppState) -> Result<Option<NaiveDate>> {
+    let date = sqlx::query_scalar!(
+        "SELECT MAX(reports.date) FROM reports
+        JOIN daily_totals ON daily_totals.report_id = reports.id"
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(date)
+}
+
+pub struct StoreRunRecord {
+    pub date: NaiveDate,
+    pub store: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub duration_ms: i32,
+    pub product_count: i32,
+}
+
+/// How each store fared in the latest report.
+pub async fn last_store_runs(state: &AppState) -> Result<Vec<StoreRunRecord>> {
+    let records = sqlx::query_as!(
+        StoreRunRecord,
+        r#"
+SELECT
+    reports.date,
+    store_runs.store,
+    store_runs.status,
+    store_runs.error,
+    store_runs.duration_ms,
+    store_runs.product_count
+FROM store_runs
+JOIN reports
+    ON reports.id = store_runs.report_id
+WHERE reports.date = (
+    SELECT MAX(date) FROM reports
+)
+ORDER BY store_runs.store"#,
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(records)
+}
+
+/// The number of failed runs per store over the last `days` days.
+pub async fn store_failures(days: i64, state: &AppState) -> Result<Vec<(String, i64)>> {
+    let since = Utc::now().date_naive() - TimeDelta::days(days);
+
+    let records = sqlx::query!(
+        r#"
+SELECT
+    store_runs.store,
+    COUNT(*) AS "failures!"
+FROM store_runs
+JOIN reports
+    ON reports.id = store_runs.report_id
+WHERE reports.date > $1
+  AND store_runs.status = 'failed'
+GROUP BY store_runs.store"#,
+        since
+    )
+    .map(|record| (record.store, record.failures))
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(records)
+}
+
+pub async fn ingredients(state: &AppState) -> Result<Vec<Ingredient>> {
+    let records = sqlx::query_as!(Ingredient, "SELECT * FROM ingredients ORDER BY id")
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(records)
+}
+
+pub async fn ingredient_by_name(state: &AppState, name: &str) -> Result<Option<Ingredient>> {
+    let record = sqlx::query_as!(
+        Ingredient,
+        "SELECT * FROM ingredients WHERE LOWER(name) = LOWER($1)",
+        name
+    )
+    .fetch_optional(&state.db)
+    .await?;
+
+    Ok(record)
+}
+
+/// How a store or ingredient is shown in plots.
+pub struct SeriesLabel {
+    /// The store id or ingredient name.
+    pub key: String,
+    pub label: String,
+    pub color: Option<String>,
+}
+
+pub async fn store_labels(state: &AppState) -> Result<Vec<SeriesLabel>> {
+    let records = sqlx::query_as!(
+        SeriesLabel,
+        r#"SELECT id AS "key!", label, color FROM stores"#
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(records)
+}
+
+pub async fn ingredient_labels(state: &AppState) -> Result<Vec<SeriesLabel>> {
+    let records = sqlx::query_as!(
+        SeriesLabel,
+        r#"SELECT name AS "key!", COALESCE(label, name) AS "label!", color FROM ingredients"#
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(records)
+}
+
+/// Inserts a new ingredient and records who added it. The `id` of `ingredient` is ignored.
+pub async fn insert_ingredient(
+    ingredient: &Ingredient,
+    user_id: i64,
+    state: &AppState,
+) -> Result<i32> {
+    let mut tx = state.db.begin().await?;
+
+    let id = sqlx::query!(
+        "INSERT INTO ingredients
+        (name, aliases, amount, coop_id, ica_category_name, willys_category_name, hemkop_category_name, mathem_category_name, color, label)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id",
+        ingredient.name,
+        ingredient.aliases,
+        ingredient.amount,
+        ingredient.coop_id,
+        ingredient.ica_category_name,
+        ingredient.willys_category_name,
+        ingredient.hemkop_category_name,
+        ingredient.mathem_category_name,
+        ingredient.color,
+        ingredient.label
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    insert_audit(id, user_id, "add", &format!("{ingredient:?}"), &mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(id)
+}
+
+pub async fn update_ingredient(
+    ingredient: &Ingredient,
+    changes: &str,
+    user_id: i64,
+    state: &AppState,
+) -> Result<()> {
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query!(
+        "UPDATE ingredients SET
+        name = $2,
+        aliases = $3,
+        amount = $4,
+        coop_id = $5,
+        ica_category_name = $6,
+        willys_category_name = $7,
+        hemkop_category_name = $8,
+        mathem_category_name = $9,
+        color = $10,
+        label = $11
+        WHERE id = $1",
+        ingredient.id,
+        ingredient.name,
+        ingredient.aliases,
+        ingredient.amount,
+        ingredient.coop_id,
+        ingredient.ica_category_name,
+        ingredient.willys_category_name,
+        ingredient.hemkop_category_name,
+        ingredient.mathem_category_name,
+        ingredient.color,
+        ingredient.label
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    insert_audit(ingredient.id, user_id, "edit", changes, &mut tx).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn insert_audit(
+    ingredient_id: i32,
+    user_id: i64,
+    action: &str,
+    changes: &str,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<()> {
+    sqlx::query!(
+        "INSERT INTO ingredient_audit (ingredient_id, user_id, action, changes)
+        VALUES ($1, $2, $3, $4)",
+        ingredient_id,
+        user_id,
+        action,
+        changes
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Something that happened which is worth marking in the plots, like "Smörkris".
+pub struct PlotEvent {
+    pub id: i32,
+    pub date: NaiveDate,
+    pub label: String,
+}
+
+pub async fn plot_events(range: &Range, state: &AppState) -> Result<Vec<PlotEvent>> {
+    let records = sqlx::query_as!(
+        PlotEvent,
+        "SELECT id, date, label FROM plot_events
+        WHERE ($1::timestamp IS NULL OR date >= $1::date)
+          AND ($2::timestamp IS NULL OR date < $2::date)
+        ORDER BY date ASC",
+        range.from,
+        range.to
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(records)
+}
+
+pub async fn insert_plot_event(
+    date: NaiveDate,
+    label: &str,
+    user_id: i64,
+    state: &AppState,
+) -> Result<i32> {
+    let id = sqlx::query_scalar!(
+        "INSERT INTO plot_events (date, label, user_id) VALUES ($1, $2, $3) RETURNING id",
+        date,
+        label,
+        user_id
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(id)
+}
+
+/// Returns the removed event, if there was one with the id.
+pub async fn delete_plot_event(id: i32, state: &AppState) -> Result<Option<PlotEvent>> {
+    let record = sqlx::query_as!(
+        PlotEvent,
+        "DELETE FROM plot_events WHERE id = $1 RETURNING id, date, label",
+        id
+    )
+    .fetch_optional(&state.db)
+    .await?;
+
+    Ok(record)
+}
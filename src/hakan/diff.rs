@@ -0,0 +1,243 @@
+//! Describes what changed between two reports.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use itertools::Itertools;
+
+use crate::AppState;
+
+use super::{
+    db::{self, SnapshotProduct},
+    Store,
+};
+
+/// Price changes smaller than this are just rounding.
+const MIN_PRICE_CHANGE: f64 = 0.005;
+
+pub struct ReportDiff {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Ingredients whose cheapest product changed.
+    pub switched: Vec<Switch>,
+    /// Ingredients whose cheapest price changed, biggest change first.
+    pub moved: Vec<PriceChange>,
+    pub appeared: Vec<ProductChange>,
+    pub disappeared: Vec<ProductChange>,
+}
+
+pub struct Switch {
+    pub ingredient: String,
+    pub from: ProductChange,
+    pub to: ProductChange,
+}
+
+pub struct PriceChange {
+    pub ingredient: String,
+    pub from: f64,
+    pub to: f64,
+}
+
+impl PriceChange {
+    pub fn difference(&self) -> f64 {
+        self.to - self.from
+    }
+
+    pub fn percent(&self) -> f64 {
+        self.difference() / self.from * 100.0
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ProductChange {
+    pub ingredient: String,
+    pub store: String,
+    pub product: String,
+}
+
+impl ProductChange {
+    fn new(product: &SnapshotProduct) -> Self {
+        let store = Store::from_id(&product.store)
+            .map(|store| store.to_string())
+            .unwrap_or_else(|| product.store.clone());
+
+        ProductChange {
+            ingredient: product.ingredient_name.clone(),
+            store,
+            product: format!("{} {}", product.manufacturer_name, product.name)
+                .trim()
+                .to_string(),
+        }
+    }
+}
+
+/// Compares the reports on or before `from` and `to`.
+///
+/// Returns `None` if either date has no report.
+pub async fn between(
+    from: NaiveDate,
+    to: NaiveDate,
+    state: &AppState,
+) -> Result<Option<ReportDiff>> {
+    let (Some(from), Some(to)) = (
+        db::snapshot(from, state).await?,
+        db::snapshot(to, state).await?,
+    ) else {
+        return Ok(None);
+    };
+
+    Ok(Some(compare(from, to)))
+}
+
+fn compare(
+    (from_date, from): (NaiveDate, Vec<SnapshotProduct>),
+    (to_date, to): (NaiveDate, Vec<SnapshotProduct>),
+) -> ReportDiff {
+    let old_cheapest = cheapest(&from);
+    let new_cheapest = cheapest(&to);
+
+    let mut switched = Vec::new();
+    let mut moved = Vec::new();
+
+    for (ingredient, new) in new_cheapest.iter().sorted_by_key(|(name, _)| *name) {
+        let Some(old) = old_cheapest.get(ingredient) else {
+            continue;
+        };
+
+        let (old_product, new_product) = (ProductChange::new(old), ProductChange::new(new));
+
+        if old_product != new_product {
+            switched.push(Switch {
+                ingredient: ingredient.to_string(),
+                from: old_product,
+                to: new_product,
+            });
+        }
+
+        if (new.price - old.price).abs() > MIN_PRICE_CHANGE {
+            moved.push(PriceChange {
+                ingredient: ingredient.to_string(),
+                from: old.price,
+                to: new.price,
+            });
+        }
+    }
+
+    moved.sort_by(|a, b| {
+        a.difference()
+            .abs()
+            .total_cmp(&b.difference().abs())
+            .reverse()
+    });
+
+    let old_products = from.iter().map(ProductChange::new).collect::<HashSet<_>>();
+    let new_products = to.iter().map(ProductChange::new).collect::<HashSet<_>>();
+
+    let sorted = |products: Vec<&ProductChange>| {
+        products
+            .into_iter()
+            .cloned()
+            .sorted_by(|a, b| (&a.ingredient, &a.store).cmp(&(&b.ingredient, &b.store)))
+            .collect_vec()
+    };
+
+    ReportDiff {
+        from: from_date,
+        to: to_date,
+        switched,
+        moved,
+        appeared: sorted(new_products.difference(&old_products).collect()),
+        disappeared: sorted(old_products.difference(&new_products).collect()),
+    }
+}
+
+fn cheapest(products: &[SnapshotProduct]) -> HashMap<&str, &SnapshotProduct> {
+    products
+        .iter()
+        .filter(|product| product.cheapest)
+        .map(|product| (product.ingredient_name.as_str(), product))
+        .collect()
+}
+
+impl ReportDiff {
+    pub fn is_empty(&self) -> bool {
+        self.switched.is_empty()
+            && self.moved.is_empty()
+            && self.appeared.is_empty()
+            && self.disappeared.is_empty()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let switched = self.switched.iter().map(|switch| {
+            format!(
+                "🔀 **{}**: {} ({}) → {} ({})",
+                switch.ingredient,
+                switch.from.product,
+                switch.from.store,
+                switch.to.product,
+                switch.to.store
+            )
+        });
+
+        let moved = self.moved.iter().map(|change| {
+            let emoji = if change.difference() > 0.0 {
+                "📈"
+            } else {
+                "📉"
+            };
+
+            format!(
+                "{emoji} **{}**: `{:0.1}kr` → `{:0.1}kr` ({:+0.1}kr, {:+0.1}%)",
+                change.ingredient,
+                change.from,
+                change.to,
+                change.difference(),
+                change.percent()
+            )
+        });
+
+        let appeared = self.appeared.iter().map(|product| {
+            format!(
+                "🆕 {} hos {} ({})",
+                product.product, product.store, product.ingredient
+            )
+        });
+
+        let disappeared = self.disappeared.iter().map(|product| {
+            format!(
+                "🗑️ {} hos {} ({})",
+                product.product, product.store, product.ingredient
+            )
+        });
+
+        switched
+            .chain(moved)
+            .chain(appeared)
+            .chain(disappeared)
+            .collect()
+    }
+
+    /// Renders the diff as lines of markdown, cutting it off to fit within `max_len` characters.
+    pub fn render(&self, max_len: usize) -> String {
+        if self.is_empty() {
+            return "Inget har ändrats.".to_string();
+        }
+
+        let lines = self.lines();
+        let mut text = String::new();
+
+        for (i, line) in lines.iter().enumerate() {
+            // leave room for the "och n till" line
+            if text.chars().count() + line.chars().count() + 32 > max_len {
+                text.push_str(&format!("-# … och {} till", lines.len() - i));
+                break;
+            }
+
+            text.push_str(line);
+            text.push('\n');
+        }
+
+        text
+    }
+}
@@ -0,0 +1,229 @@
+//! Exports the price history as CSV or JSON.
+
+use anyhow::{bail, Result};
+use chrono::{NaiveDateTime, TimeDelta};
+use serde_json::{json, Map, Value};
+
+use crate::AppState;
+
+use super::db::{self, Range, Resolution};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Series {
+    /// The total price of the cheapest products.
+    Total,
+    /// The total price per store.
+    Store,
+    /// The cheapest price per ingredient.
+    Ingredient,
+    /// Every stored product.
+    Products,
+}
+
+impl Series {
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "total" => Some(Series::Total),
+            "butik" => Some(Series::Store),
+            "ingrediens" => Some(Series::Ingredient),
+            "produkter" => Some(Series::Products),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Series::Total => "total",
+            Series::Store => "butik",
+            Series::Ingredient => "ingrediens",
+            Series::Products => "produkter",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "csv" => Some(Format::Csv),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+        }
+    }
+}
+
+struct Table {
+    columns: &'static [&'static str],
+    rows: Vec<Vec<Value>>,
+}
+
+pub async fn export(
+    series: Series,
+    format: Format,
+    range: &Range,
+    state: &AppState,
+) -> Result<String> {
+    let table = match series {
+        Series::Total => Table {
+            columns: &["date", "price"],
+            rows: db::reports(range, Resolution::Day, state)
+                .await?
+                .into_iter()
+                .map(|report| vec![date(report.created_at), json!(report.price)])
+                .collect(),
+        },
+        Series::Store => Table {
+            columns: &["date", "store", "price"],
+            rows: db::reports_by_store(range, Resolution::Day, state)
+                .await?
+                .into_iter()
+                .map(|report| {
+                    vec![
+                        date(report.created_at),
+                        json!(report.store),
+                        json!(report.price),
+                    ]
+                })
+                .collect(),
+        },
+        Series::Ingredient => Table {
+            columns: &["date", "ingredient", "price"],
+            rows: db::reports_by_ingredient(range, Resolution::Day, state)
+                .await?
+                .into_iter()
+                .map(|report| {
+                    vec![
+                        date(report.created_at),
+                        json!(report.ingredient_name),
+                        json!(report.price),
+                    ]
+                })
+                .collect(),
+        },
+        Series::Products => Table {
+            columns: &[
+                "created_at",
+                "store",
+                "ingredient",
+                "name",
+                "manufacturer_name",
+                "comparative_price",
+                "comparative_price_text",
+                "price",
+                "shelf_price",
+                "package_size",
+                "url",
+                "source",
+                "anomaly",
+            ],
+            rows: products(range, state)
+                .await?
+                .into_iter()
+                .map(|product| {
+                    vec![
+                        json!(product.created_at.format("%Y-%m-%dT%H:%M:%S").to_string()),
+                        json!(product.store),
+                        json!(product.ingredient_name),
+                        json!(product.name),
+                        json!(product.manufacturer_name),
+                        json!(product.comparative_price),
+                        json!(product.comparative_price_text),
+                        json!(product.price),
+                        json!(product.shelf_price),
+                        json!(product.package_size),
+                        json!(product.url),
+                        json!(product.source),
+                        json!(product.anomaly),
+                    ]
+                })
+                .collect(),
+        },
+    };
+
+    if table.rows.is_empty() {
+        bail!("no data in the given range");
+    }
+
+    Ok(match format {
+        Format::Csv => to_csv(&table),
+        Format::Json => to_json(&table)?,
+    })
+}
+
+/// The products of `range`, refusing ranges that reach back into pruned reports
+/// instead of silently leaving them out.
+async fn products(range: &Range, state: &AppState) -> Result<Vec<db::ProductRecord>> {
+    if let Some(until) = db::downsampled_until(state).await? {
+        if range.from.is_none_or(|from| from.date() <= until) {
+            bail!(
+                "products up to {until} have been pruned, export from {} onwards",
+                until + TimeDelta::days(1)
+            );
+        }
+    }
+
+    db::products(range, state).await
+}
+
+fn date(date: NaiveDateTime) -> Value {
+    json!(date.format("%Y-%m-%d").to_string())
+}
+
+fn to_csv(table: &Table) -> String {
+    let mut csv = table.columns.join(",");
+    csv.push('\n');
+
+    for row in &table.rows {
+        let line = row
+            .iter()
+            .map(|value| match value {
+                Value::Null => String::new(),
+                Value::String(text) => escape_csv(text),
+                value => value.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        csv.push_str(&line);
+        csv.push('\n');
+    }
+
+    csv
+}
+
+fn escape_csv(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn to_json(table: &Table) -> Result<String> {
+    let objects = table
+        .rows
+        .iter()
+        .map(|row| {
+            table
+                .columns
+                .iter()
+                .map(|column| column.to_string())
+                .zip(row.iter().cloned())
+                .collect::<Map<_, _>>()
+        })
+        .collect::<Vec<_>>();
+
+    Ok(serde_json::to_string_pretty(&objects)?)
+}
@@ -0,0 +1,118 @@
+//! Predicts where the total Håkan index is heading by fitting a linear trend
+//! to the recent daily totals.
+
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::AppState;
+
+use super::db::{self, Range, Resolution};
+
+/// How many days of history the trend is fitted to.
+const HISTORY_DAYS: i64 = 60;
+/// We need at least this many days to say anything about the trend.
+const MIN_HISTORY: usize = 7;
+/// Two-sided 95% interval for a normal distribution.
+const Z_95: f64 = 1.96;
+
+pub struct ForecastPoint {
+    pub date: DateTime<Utc>,
+    pub price: f64,
+    /// Lower bound of the 95% prediction interval.
+    pub lower: f64,
+    /// Upper bound of the 95% prediction interval.
+    pub upper: f64,
+}
+
+pub struct Forecast {
+    /// Starts at the last known day and then one point per predicted day.
+    pub points: Vec<ForecastPoint>,
+}
+
+impl Forecast {
+    /// Whether `price` is lower than what the total is expected to average over the forecast.
+    pub fn is_good_day_to_buy(&self, price: f64) -> bool {
+        let predicted = &self.points[1..];
+        let average =
+            predicted.iter().map(|point| point.price).sum::<f64>() / predicted.len() as f64;
+
+        price < average
+    }
+}
+
+/// Forecasts the total price `days` days ahead.
+///
+/// Returns `None` if there isn't enough recent history.
+pub async fn total(days: i64, state: &AppState) -> Result<Option<Forecast>> {
+    let range = Range {
+        from: Some(Utc::now().naive_utc() - TimeDelta::days(HISTORY_DAYS)),
+        to: None,
+    };
+
+    let reports = db::reports(&range, Resolution::Day, state).await?;
+
+    let series = reports
+        .iter()
+        .map(|report| (report.created_at.and_utc(), report.price))
+        .collect::<Vec<_>>();
+
+    Ok(linear(&series, days))
+}
+
+/// Fits a least squares line through `series` and extrapolates it `days` days past its end,
+/// with a prediction interval based on the residuals.
+fn linear(series: &[(DateTime<Utc>, f64)], days: i64) -> Option<Forecast> {
+    if series.len() < MIN_HISTORY || days < 1 {
+        return None;
+    }
+
+    let (start, _) = series[0];
+    let (end, _) = *series.last()?;
+
+    let to_x = |date: DateTime<Utc>| (date - start).num_seconds() as f64 / 86400.0;
+
+    let n = series.len() as f64;
+    let mean_x = series.iter().map(|(date, _)| to_x(*date)).sum::<f64>() / n;
+    let mean_y = series.iter().map(|(_, price)| price).sum::<f64>() / n;
+
+    let sxx: f64 = series
+        .iter()
+        .map(|(date, _)| (to_x(*date) - mean_x).powi(2))
+        .sum();
+    let sxy: f64 = series
+        .iter()
+        .map(|(date, price)| (to_x(*date) - mean_x) * (price - mean_y))
+        .sum();
+
+    if sxx == 0.0 {
+        return None;
+    }
+
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+
+    let residual_variance = series
+        .iter()
+        .map(|(date, price)| (price - (intercept + slope * to_x(*date))).powi(2))
+        .sum::<f64>()
+        / (n - 2.0);
+    let residual_std = residual_variance.sqrt();
+
+    let points = (0..=days)
+        .map(|day| {
+            let date = end + TimeDelta::days(day);
+            let x = to_x(date);
+            let price = intercept + slope * x;
+            let margin = Z_95 * residual_std * (1.0 + 1.0 / n + (x - mean_x).powi(2) / sxx).sqrt();
+
+            ForecastPoint {
+                date,
+                price,
+                lower: price - margin,
+                upper: price + margin,
+            }
+        })
+        .collect();
+
+    Some(Forecast { points })
+}
@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::AppState;
 
-use super::{Ingredient, Product};
+use super::{CategoryCandidate, Ingredient, Product, ProductSource};
 
 const STORE_ID: u32 = 1003823;
 
@@ -21,6 +21,15 @@ struct ProductsResponse {
     result: IcaResult,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SearchResponse {
+    entities: Entities,
+    /// The categories of the products, if the search includes them.
+    #[serde(default)]
+    result: Option<IcaResult>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 struct Entities {
@@ -174,10 +183,57 @@ pub async fn get_products(
         .json()
         .await?;
 
-    let products = result.entities.product.into_iter().map(|(_, product)| {
-        let comparative_price = product.price.unit.current.amount.parse()?;
+    let products = result
+        .entities
+        .product
+        .into_values()
+        .map(|product| product.into_product(ingredient.amount, ProductSource::Category));
+
+    products.collect::<Result<Vec<_>, _>>().map(Vec::into_iter)
+}
+
+pub async fn search_products(
+    ingredient: &Ingredient,
+    state: &AppState,
+) -> Result<impl Iterator<Item = Product>> {
+    let mut products = Vec::new();
+
+    for term in ingredient.search_terms() {
+        let result = search_products_raw(term, 20, state).await?;
+
+        for product in result.entities.product.into_values() {
+            products.push(product.into_product(ingredient.amount, ProductSource::Search)?);
+        }
+    }
+
+    Ok(products.into_iter())
+}
 
-        let comparative_price_text = match product.price.unit.label.as_str() {
+async fn search_products_raw(term: &str, count: u32, state: &AppState) -> Result<SearchResponse> {
+    let url = format!("{BASE_URL}/stores/{STORE_ID}/api/v6/products/search");
+
+    let result = state
+        .http
+        .get(url)
+        .query(&[("limit", count.to_string().as_str()), ("term", term)])
+        .header(
+            "User-Agent",
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:141.0) Gecko/20100101 Firefox/141.0",
+        )
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(result)
+}
+
+impl IcaProduct {
+    fn into_product(self, amount: f64, source: ProductSource) -> Result<Product> {
+        let comparative_price = self.price.unit.current.amount.parse()?;
+
+        let comparative_price_text = match self.price.unit.label.as_str() {
             "fop.price.per.kg" => "kr/kg",
             "fop.price.per.each" => "kr/st",
             label => bail!("unknown unit price label: {label}"),
@@ -186,19 +242,77 @@ pub async fn get_products(
 
         let url = format!(
             "{BASE_URL}/stores/{STORE_ID}/products/{}/{}",
-            product.name.to_case(convert_case::Case::Kebab),
-            product.retailer_product_id
+            self.name.to_case(convert_case::Case::Kebab),
+            self.retailer_product_id
         );
 
+        let shelf_price = self.price.current.amount.parse().ok();
+        let package_size = self.size.map(|size| size.value);
+
         Ok(Product {
             comparative_price,
             comparative_price_text,
-            price: ingredient.amount * comparative_price,
-            name: product.name,
-            manufacturer_name: product.brand,
+            price: amount * comparative_price,
+            shelf_price,
+            package_size,
+            name: self.name,
+            manufacturer_name: self.brand,
             url,
+            source,
         })
-    });
+    }
+}
 
-    products.collect::<Result<Vec<_>, _>>().map(Vec::into_iter)
+pub async fn discover_categories(
+    keyword: &str,
+    state: &AppState,
+) -> Result<Vec<CategoryCandidate>> {
+    let SearchResponse { entities, result } = search_products_raw(keyword, 50, state).await?;
+
+    let names: HashMap<&str, &str> = result
+        .iter()
+        .flat_map(|result| &result.categories)
+        .flat_map(|category| {
+            [
+                (category.id.as_str(), category.name.as_str()),
+                (
+                    category.retailer_category_id.as_str(),
+                    category.name.as_str(),
+                ),
+            ]
+        })
+        .collect();
+
+    // the category path is a list of category ids from the root down,
+    // the last one is what goes in `ica_category_name`
+    let candidates = entities
+        .product
+        .into_values()
+        .filter_map(|product| Some((product.category_path.last()?.clone(), product)))
+        .into_group_map()
+        .into_iter()
+        .map(|(id, products)| {
+            // ids without a known name are shown as they are, marked as ids
+            let name = products[0]
+                .category_path
+                .iter()
+                .map(|id| match names.get(id.as_str()) {
+                    Some(name) => name.to_string(),
+                    None => format!("id {id}"),
+                })
+                .join(" > ");
+            let samples = products
+                .iter()
+                .take(super::DISCOVERY_SAMPLE_COUNT)
+                .map(|product| format!("{} {}", product.brand, product.name))
+                .collect();
+
+            (products.len(), CategoryCandidate { id, name, samples })
+        })
+        .sorted_by(|(a, _), (b, _)| b.cmp(a))
+        .map(|(_, candidate)| candidate)
+        .take(super::DISCOVERY_MAX_CANDIDATES)
+        .collect();
+
+    Ok(candidates)
 }
@@ -1,27 +1,105 @@
 use anyhow::{Context, Result};
+use itertools::Itertools;
 use scraper::{Html, Selector};
 
 use crate::AppState;
 
-use super::{Ingredient, Product};
+use super::{CategoryCandidate, Ingredient, Product, ProductSource};
+
+const CATEGORIES_URL: &str = "https://www.mathem.se/se/categories/";
+const SEARCH_URL: &str = "https://www.mathem.se/se/search/products/";
 
 pub async fn get_products(
     ingredient: &Ingredient,
     state: &AppState,
 ) -> Result<impl Iterator<Item = Product>> {
-    let url = format!(
-        "https://www.mathem.se/se/categories/{}",
-        ingredient.mathem_category_name
-    );
+    let url = format!("{CATEGORIES_URL}{}", ingredient.mathem_category_name);
+
+    scrape_products(
+        state.http.get(url),
+        ingredient.amount,
+        ProductSource::Category,
+    )
+    .await
+    .map(Vec::into_iter)
+}
+
+pub async fn search_products(
+    ingredient: &Ingredient,
+    state: &AppState,
+) -> Result<impl Iterator<Item = Product>> {
+    let mut products = Vec::new();
+
+    for term in ingredient.search_terms() {
+        let request = state.http.get(SEARCH_URL).query(&[("q", term)]);
+        products.extend(scrape_products(request, ingredient.amount, ProductSource::Search).await?);
+    }
+
+    Ok(products.into_iter())
+}
+
+/// Walks the links on the category overview and returns those whose text contains `keyword`.
+pub async fn discover_categories(
+    keyword: &str,
+    state: &AppState,
+) -> Result<Vec<CategoryCandidate>> {
     let html = state
         .http
-        .get(url)
+        .get(CATEGORIES_URL)
         .send()
         .await?
         .error_for_status()?
         .text()
         .await?;
 
+    let link_selector = Selector::parse("a[href*='/se/categories/']").unwrap();
+
+    let links = {
+        let document = Html::parse_document(&html);
+
+        document
+            .select(&link_selector)
+            .filter_map(|link| {
+                let name = link.text().collect::<String>().trim().to_string();
+                let id = link
+                    .attr("href")?
+                    .split_once("/se/categories/")?
+                    .1
+                    .trim_matches('/')
+                    .to_string();
+
+                Some((id, name))
+            })
+            .filter(|(id, name)| !id.is_empty() && name.to_lowercase().contains(keyword))
+            .unique_by(|(id, _)| id.clone())
+            .take(super::DISCOVERY_MAX_CANDIDATES)
+            .collect_vec()
+    };
+
+    let mut candidates = Vec::new();
+
+    for (id, name) in links {
+        let request = state.http.get(format!("{CATEGORIES_URL}{id}"));
+        let samples = scrape_products(request, 1.0, ProductSource::Category)
+            .await?
+            .into_iter()
+            .take(super::DISCOVERY_SAMPLE_COUNT)
+            .map(|product| format!("{} {}", product.manufacturer_name, product.name))
+            .collect();
+
+        candidates.push(CategoryCandidate { id, name, samples });
+    }
+
+    Ok(candidates)
+}
+
+async fn scrape_products(
+    request: reqwest::RequestBuilder,
+    amount: f64,
+    source: ProductSource,
+) -> Result<Vec<Product>> {
+    let html = request.send().await?.error_for_status()?.text().await?;
+
     let product_selector = Selector::parse("article.k-card").unwrap();
     let name_selector = Selector::parse("a").unwrap();
     let manufacturer_selector = Selector::parse(".styles_ProductNameExtraTile__yZuNO").unwrap();
@@ -87,9 +165,12 @@ pub async fn get_products(
             comparative_price,
             comparative_price_text,
             url,
-            price: comparative_price * ingredient.amount,
+            price: comparative_price * amount,
+            shelf_price: None,
+            package_size: None,
+            source,
         });
     }
 
-    Ok(products.into_iter())
+    Ok(products)
 }
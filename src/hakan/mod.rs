@@ -1,20 +1,35 @@
-use std::{cmp::Ordering, collections::HashMap, fmt::Display, future::Future};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::Display,
+    future::Future,
+    time::{Duration, Instant},
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use itertools::Itertools;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::AppState;
 
+pub mod anomaly;
 mod axfood;
+pub mod backfill;
+pub mod cleanup;
 mod coop;
+pub mod cpi;
 pub mod db;
+pub mod diff;
+pub mod export;
+pub mod forecast;
 mod ica;
 mod mathem;
+pub mod optimize;
 pub mod plot;
+pub mod retention;
 pub mod update;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Ingredient {
     pub id: i32,
     pub name: String,
@@ -25,6 +40,45 @@ pub struct Ingredient {
     pub willys_category_name: String,
     pub hemkop_category_name: String,
     pub mathem_category_name: String,
+    /// `#rrggbb` colour of the ingredient in plots.
+    pub color: Option<String>,
+    /// Shown in plot legends instead of the name.
+    pub label: Option<String>,
+}
+
+impl Ingredient {
+    /// The id of the category we browse for this ingredient in the given store.
+    pub fn category(&self, store: Store) -> String {
+        match store {
+            Store::Coop => self.coop_id.to_string(),
+            Store::Ica => self.ica_category_name.clone(),
+            Store::Willys => self.willys_category_name.clone(),
+            Store::Hemkop => self.hemkop_category_name.clone(),
+            Store::Mathem => self.mathem_category_name.clone(),
+        }
+    }
+
+    /// The name followed by every alias, used when searching stores.
+    pub fn search_terms(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str())
+            .chain(self.aliases.split(',').filter(|alias| !alias.is_empty()))
+    }
+
+    pub fn set_category(&mut self, store: Store, category: String) -> Result<()> {
+        match store {
+            Store::Coop => {
+                self.coop_id = category
+                    .parse()
+                    .map_err(|_| anyhow!("coop category id must be a number"))?
+            }
+            Store::Ica => self.ica_category_name = category,
+            Store::Willys => self.willys_category_name = category,
+            Store::Hemkop => self.hemkop_category_name = category,
+            Store::Mathem => self.mathem_category_name = category,
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -34,9 +88,52 @@ pub struct Product {
     pub comparative_price: f64,
     pub comparative_price_text: String,
     pub url: String,
+    /// The price of the amount of this product that goes into the recipe.
     pub price: f64,
+    /// The price of a single package, if the store tells us.
+    pub shelf_price: Option<f64>,
+    pub package_size: Option<String>,
+    pub source: ProductSource,
+}
+
+/// How a product was found in the store.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ProductSource {
+    /// Browsing the ingredient's category.
+    Category,
+    /// Searching for the ingredient's name or aliases.
+    Search,
+}
+
+impl ProductSource {
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "category" => Some(ProductSource::Category),
+            "search" => Some(ProductSource::Search),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            ProductSource::Category => "category",
+            ProductSource::Search => "search",
+        }
+    }
 }
 
+/// A store category that might fit an ingredient, found by [`discover_categories`].
+#[derive(Debug)]
+pub struct CategoryCandidate {
+    /// The value to put in the ingredient's category column for the store.
+    pub id: String,
+    pub name: String,
+    pub samples: Vec<String>,
+}
+
+const DISCOVERY_SAMPLE_COUNT: usize = 3;
+const DISCOVERY_MAX_CANDIDATES: usize = 10;
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Store {
     Coop,
@@ -59,6 +156,18 @@ impl Display for Store {
 }
 
 impl Store {
+    pub const ALL: [Store; 5] = [
+        Store::Coop,
+        Store::Ica,
+        Store::Willys,
+        Store::Hemkop,
+        Store::Mathem,
+    ];
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|store| store.id() == id)
+    }
+
     pub fn id(&self) -> &'static str {
         match self {
             Store::Coop => "coop",
@@ -68,31 +177,70 @@ impl Store {
             Store::Mathem => "mathem",
         }
     }
+
+    /// Whether the store only delivers, meaning we pay a delivery fee instead of visiting it.
+    pub fn is_online(&self) -> bool {
+        matches!(self, Store::Mathem)
+    }
 }
 
 pub struct Report {
     pub ingredients: HashMap<i32, Ingredient>,
     pub stores: HashMap<Store, HashMap<i32, Product>>,
+    /// Products with suspicious prices, keyed by store and ingredient id, along with
+    /// the comparative price they usually have. See [`anomaly::detect`].
+    pub anomalies: HashMap<(Store, i32), f64>,
+    /// How scraping each store went, including the ones that failed.
+    pub runs: Vec<StoreRun>,
+}
+
+pub struct StoreRun {
+    pub store: Store,
+    pub duration: Duration,
+    pub product_count: usize,
+    /// Set if the store failed and has no products in the report.
+    pub error: Option<String>,
+}
+
+impl StoreRun {
+    pub fn status(&self) -> &'static str {
+        match self.error {
+            Some(_) => "failed",
+            None => "ok",
+        }
+    }
 }
 
 impl Report {
+    /// The product for `ingredient` in every store that has one.
     pub fn products_by_ingredient<'a>(
         &'a self,
         ingredient: &'a Ingredient,
     ) -> impl Iterator<Item = (Store, &'a Product)> + 'a {
-        self.stores
-            .iter()
-            .map(|(store, products)| (*store, &products[&ingredient.id]))
+        self.stores.iter().filter_map(|(store, products)| {
+            let product = products.get(&ingredient.id)?;
+            Some((*store, product))
+        })
     }
 
+    pub fn is_anomaly(&self, store: Store, ingredient: &Ingredient) -> bool {
+        self.anomalies.contains_key(&(store, ingredient.id))
+    }
+
+    /// The cheapest product for each ingredient. Anomalous products are only picked
+    /// if every store's product is anomalous, and ingredients that no store has are
+    /// left out.
     pub fn cheapest(&self) -> impl Iterator<Item = (&Ingredient, Store, &Product)> {
-        self.ingredients.values().map(|ingredient| {
-            let (store, product) = self
-                .products_by_ingredient(ingredient)
-                .min_by(|(_, a), (_, b)| a.comparative_price.total_cmp(&b.comparative_price))
-                .unwrap();
+        self.ingredients.values().filter_map(|ingredient| {
+            let (store, product) =
+                self.products_by_ingredient(ingredient)
+                    .min_by(|(a_store, a), (b_store, b)| {
+                        self.is_anomaly(*a_store, ingredient)
+                            .cmp(&self.is_anomaly(*b_store, ingredient))
+                            .then(a.comparative_price.total_cmp(&b.comparative_price))
+                    })?;
 
-            (ingredient, store, product)
+            Some((ingredient, store, product))
         })
     }
 }
@@ -103,20 +251,21 @@ pub async fn create_report(state: &AppState) -> Result<Report> {
         .await?;
 
     let mut stores = HashMap::new();
+    let mut runs = Vec::new();
 
     let (coop, ica, willys, hemkop, mathem) = tokio::join!(
-        create_store_report(coop::get_products, &ingredients, state),
-        create_store_report(ica::get_products, &ingredients, state),
-        create_store_report(axfood::get_willys_products, &ingredients, state),
-        create_store_report(axfood::get_hemkop_products, &ingredients, state),
-        create_store_report(mathem::get_products, &ingredients, state),
+        timed(create_store_report(Store::Coop, &ingredients, state)),
+        timed(create_store_report(Store::Ica, &ingredients, state)),
+        timed(create_store_report(Store::Willys, &ingredients, state)),
+        timed(create_store_report(Store::Hemkop, &ingredients, state)),
+        timed(create_store_report(Store::Mathem, &ingredients, state)),
     );
 
-    insert_store_report(&mut stores, Store::Coop, coop);
-    insert_store_report(&mut stores, Store::Ica, ica);
-    insert_store_report(&mut stores, Store::Willys, willys);
-    insert_store_report(&mut stores, Store::Hemkop, hemkop);
-    insert_store_report(&mut stores, Store::Mathem, mathem);
+    insert_store_report(&mut stores, &mut runs, Store::Coop, coop);
+    insert_store_report(&mut stores, &mut runs, Store::Ica, ica);
+    insert_store_report(&mut stores, &mut runs, Store::Willys, willys);
+    insert_store_report(&mut stores, &mut runs, Store::Hemkop, hemkop);
+    insert_store_report(&mut stores, &mut runs, Store::Mathem, mathem);
 
     let ingredients = ingredients
         .into_iter()
@@ -126,69 +275,306 @@ pub async fn create_report(state: &AppState) -> Result<Report> {
     Ok(Report {
         stores,
         ingredients,
+        anomalies: HashMap::new(),
+        runs,
     })
 }
 
-async fn create_store_report<'a, F, R, Fut>(
+/// Loads the latest saved report that still has its products.
+///
+/// Anomalous products are left out, so stores might be missing some ingredients,
+/// and [`Report::runs`] is empty.
+pub async fn load_last_report(state: &AppState) -> Result<Option<Report>> {
+    let records = sqlx::query!(
+        r#"
+SELECT
+    products.ingredient_id,
+    products.store,
+    products.name,
+    products.manufacturer_name,
+    products.comparative_price,
+    products.comparative_price_text,
+    products.url,
+    products.price,
+    products.shelf_price,
+    products.package_size,
+    products.source
+FROM products
+JOIN reports
+    ON reports.id = products.report_id
+WHERE reports.date = (
+    SELECT MAX(date) FROM reports
+    WHERE EXISTS (SELECT 1 FROM products WHERE products.report_id = reports.id)
+)
+  AND NOT products.anomaly"#
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    if records.is_empty() {
+        return Ok(None);
+    }
+
+    let mut stores: HashMap<Store, HashMap<i32, Product>> = HashMap::new();
+
+    for record in records {
+        let Some(store) = Store::from_id(&record.store) else {
+            continue;
+        };
+
+        let product = Product {
+            name: record.name,
+            manufacturer_name: record.manufacturer_name,
+            comparative_price: record.comparative_price,
+            comparative_price_text: record.comparative_price_text,
+            url: record.url,
+            price: record.price,
+            shelf_price: record.shelf_price,
+            package_size: record.package_size,
+            source: ProductSource::from_id(&record.source).unwrap_or(ProductSource::Category),
+        };
+
+        stores
+            .entry(store)
+            .or_default()
+            .insert(record.ingredient_id, product);
+    }
+
+    let ingredients = db::ingredients(state)
+        .await?
+        .into_iter()
+        .filter(|ingredient| {
+            stores
+                .values()
+                .any(|products| products.contains_key(&ingredient.id))
+        })
+        .map(|ingredient| (ingredient.id, ingredient))
+        .collect();
+
+    Ok(Some(Report {
+        ingredients,
+        stores,
+        anomalies: HashMap::new(),
+        runs: Vec::new(),
+    }))
+}
+
+async fn timed<T>(future: impl Future<Output = T>) -> (T, Duration) {
+    let start = Instant::now();
+    let output = future.await;
+    (output, start.elapsed())
+}
+
+async fn create_store_report(
+    store: Store,
+    ingredients: &[Ingredient],
+    state: &AppState,
+) -> Result<HashMap<i32, Product>> {
+    let mut result = HashMap::new();
+    for ingredient in ingredients {
+        let product = fetch_product(store, ingredient, state).await?;
+        result.insert(ingredient.id, product);
+    }
+    Ok(result)
+}
+
+/// Fetches the cheapest product for a single ingredient from a store.
+///
+/// The ingredient's category is browsed first. If that fails or doesn't contain any
+/// matching products we fall back to searching for the ingredient's name and aliases.
+pub async fn fetch_product(
+    store: Store,
+    ingredient: &Ingredient,
+    state: &AppState,
+) -> Result<Product> {
+    match store {
+        Store::Coop => {
+            find_product(coop::get_products, coop::search_products, ingredient, state).await
+        }
+        Store::Ica => {
+            find_product(ica::get_products, ica::search_products, ingredient, state).await
+        }
+        Store::Willys => {
+            find_product(
+                axfood::get_willys_products,
+                axfood::search_willys_products,
+                ingredient,
+                state,
+            )
+            .await
+        }
+        Store::Hemkop => {
+            find_product(
+                axfood::get_hemkop_products,
+                axfood::search_hemkop_products,
+                ingredient,
+                state,
+            )
+            .await
+        }
+        Store::Mathem => {
+            find_product(
+                mathem::get_products,
+                mathem::search_products,
+                ingredient,
+                state,
+            )
+            .await
+        }
+    }
+}
+
+/// Searches a store for categories matching `keyword`, e.g. "smör", along with a few
+/// sample products from each.
+pub async fn discover_categories(
+    store: Store,
+    keyword: &str,
+    state: &AppState,
+) -> Result<Vec<CategoryCandidate>> {
+    let keyword = keyword.to_lowercase();
+
+    match store {
+        Store::Coop => coop::discover_categories(&keyword, state).await,
+        Store::Ica => ica::discover_categories(&keyword, state).await,
+        Store::Willys => axfood::discover_willys_categories(&keyword, state).await,
+        Store::Hemkop => axfood::discover_hemkop_categories(&keyword, state).await,
+        Store::Mathem => mathem::discover_categories(&keyword, state).await,
+    }
+}
+
+async fn find_product<'a, F, S, R, T, FFut, SFut>(
     reporter: F,
-    ingredients: &'a [Ingredient],
+    searcher: S,
+    ingredient: &'a Ingredient,
     state: &'a AppState,
-) -> Result<HashMap<i32, Product>>
+) -> Result<Product>
 where
-    F: Fn(&'a Ingredient, &'a AppState) -> Fut,
-    Fut: Future<Output = Result<R>>,
+    F: Fn(&'a Ingredient, &'a AppState) -> FFut,
+    S: Fn(&'a Ingredient, &'a AppState) -> SFut,
+    FFut: Future<Output = Result<R>>,
+    SFut: Future<Output = Result<T>>,
     R: Iterator<Item = Product>,
+    T: Iterator<Item = Product>,
 {
-    let mut result = HashMap::new();
-    for ingredient in ingredients {
-        let product = reporter(ingredient, state)
-            .await?
-            .filter(|product| {
-                let first_word = product
-                    .name
-                    .split_once(' ')
-                    .map(|(word, _)| word)
-                    .unwrap_or(&product.name);
-
-                first_word == ingredient.name
-                    || ingredient
-                        .aliases
-                        .split(',')
-                        .any(|alias| first_word == alias)
-            })
-            .sorted_by(|a, b| {
-                a.comparative_price
-                    .partial_cmp(&b.comparative_price)
-                    .unwrap_or(Ordering::Equal)
-            })
-            .next()
-            .ok_or_else(|| anyhow!("no products found for {}", ingredient.name))?;
+    match reporter(ingredient, state).await {
+        Ok(products) => {
+            if let Some(product) = cheapest_match(products, ingredient) {
+                return Ok(product);
+            }
 
-        result.insert(ingredient.id, product);
+            warn!(
+                ingredient = ingredient.name,
+                "no products found in category, falling back to search"
+            );
+        }
+        Err(err) => warn!(
+            ingredient = ingredient.name,
+            "failed to fetch category, falling back to search: {err:#}"
+        ),
     }
-    Ok(result)
+
+    let products = searcher(ingredient, state)
+        .await
+        .with_context(|| format!("failed to search for {}", ingredient.name))?;
+
+    cheapest_match(products, ingredient)
+        .ok_or_else(|| anyhow!("no products found for {}", ingredient.name))
+}
+
+fn cheapest_match(
+    products: impl Iterator<Item = Product>,
+    ingredient: &Ingredient,
+) -> Option<Product> {
+    products
+        .filter(|product| {
+            let first_word = product
+                .name
+                .split_once(' ')
+                .map(|(word, _)| word)
+                .unwrap_or(&product.name);
+
+            first_word == ingredient.name
+                || ingredient
+                    .aliases
+                    .split(',')
+                    .any(|alias| first_word == alias)
+        })
+        .sorted_by(|a, b| {
+            a.comparative_price
+                .partial_cmp(&b.comparative_price)
+                .unwrap_or(Ordering::Equal)
+        })
+        .next()
 }
 
 fn insert_store_report(
     map: &mut HashMap<Store, HashMap<i32, Product>>,
+    runs: &mut Vec<StoreRun>,
     store: Store,
-    res: Result<HashMap<i32, Product>>,
+    (res, duration): (Result<HashMap<i32, Product>>, Duration),
 ) {
+    let mut run = StoreRun {
+        store,
+        duration,
+        product_count: 0,
+        error: None,
+    };
+
     match res {
         Ok(products) => {
+            run.product_count = products.len();
             map.insert(store, products);
         }
-        Err(err) => error!(store = store.id(), "failed to create report: {err:#}"),
+        Err(err) => {
+            error!(store = store.id(), "failed to create report: {err:#}");
+            run.error = Some(format!("{err:#}"));
+        }
     }
+
+    runs.push(run);
 }
 
+/// Saves `report` as today's report, replacing it if one has already been saved today.
 pub async fn save_report(report: &Report, state: &AppState) -> Result<()> {
     let mut tx = state.db.begin().await?;
 
-    let report_id = sqlx::query!("INSERT INTO reports DEFAULT VALUES RETURNING id")
-        .fetch_one(&mut *tx)
+    let report_id = sqlx::query!(
+        "INSERT INTO reports DEFAULT VALUES
+        ON CONFLICT (date) DO UPDATE SET created_at = CURRENT_TIMESTAMP, imported = FALSE
+        RETURNING id"
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .id;
+
+    let replaced = sqlx::query!("DELETE FROM products WHERE report_id = $1", report_id)
+        .execute(&mut *tx)
         .await?
-        .id;
+        .rows_affected();
+
+    if replaced > 0 {
+        info!(report_id, replaced, "replacing today's report");
+    }
+
+    sqlx::query!("DELETE FROM store_runs WHERE report_id = $1", report_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for run in &report.runs {
+        sqlx::query!(
+            "INSERT INTO store_runs
+            (report_id, store, status, error, duration_ms, product_count)
+            VALUES ($1, $2, $3, $4, $5, $6)",
+            report_id,
+            run.store.id(),
+            run.status(),
+            run.error,
+            run.duration.as_millis() as i32,
+            run.product_count as i32
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
 
     for (store, products) in &report.stores {
         for (ingredient_id, product) in products {
@@ -198,15 +584,20 @@ pub async fn save_report(report: &Report, state: &AppState) -> Result<()> {
                 comparative_price,
                 comparative_price_text,
                 url,
-                ..
+                price,
+                shelf_price,
+                package_size,
+                source,
             } = product;
 
             let store_name = store.id();
+            let ingredient_amount = report.ingredients[ingredient_id].amount;
+            let anomaly = report.anomalies.contains_key(&(*store, *ingredient_id));
 
             sqlx::query!(
                 "INSERT INTO products 
-                (report_id, ingredient_id, name, manufacturer_name, comparative_price, comparative_price_text, url, store)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                (report_id, ingredient_id, name, manufacturer_name, comparative_price, comparative_price_text, url, store, source, price, shelf_price, package_size, ingredient_amount, anomaly)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
                 report_id,
                 ingredient_id,
                 name,
@@ -214,7 +605,13 @@ pub async fn save_report(report: &Report, state: &AppState) -> Result<()> {
                 comparative_price,
                 comparative_price_text,
                 url,
-                store_name
+                store_name,
+                source.id(),
+                price,
+                *shelf_price,
+                package_size.as_deref(),
+                ingredient_amount,
+                anomaly
             )
             .execute(&mut *tx)
             .await?;
@@ -223,5 +620,8 @@ pub async fn save_report(report: &Report, state: &AppState) -> Result<()> {
 
     tx.commit().await?;
 
+    // the report might have replaced today's, which the cache keys can't tell apart
+    state.plot_cache.clear();
+
     Ok(())
 }
@@ -0,0 +1,114 @@
+use itertools::Itertools;
+
+use super::{Ingredient, Product, Report, Store};
+
+/// The extra cost of spreading the shopping over several stores.
+#[derive(Debug, Clone, Copy)]
+pub struct Penalty {
+    /// Added for every physical store visited after the first one.
+    pub per_store: f64,
+    /// Added for every online store used, e.g. Mathem's delivery fee.
+    pub delivery_fee: f64,
+}
+
+impl Default for Penalty {
+    fn default() -> Self {
+        Self {
+            per_store: 20.0,
+            delivery_fee: 49.0,
+        }
+    }
+}
+
+impl Penalty {
+    fn cost(&self, stores: &[Store]) -> f64 {
+        let online = stores.iter().filter(|store| store.is_online()).count();
+        let extra_stores = (stores.len() - online).saturating_sub(1);
+
+        extra_stores as f64 * self.per_store + online as f64 * self.delivery_fee
+    }
+}
+
+pub struct ShoppingList<'a> {
+    pub stores: Vec<(Store, Vec<(&'a Ingredient, &'a Product)>)>,
+    pub product_price: f64,
+    pub penalty: f64,
+}
+
+impl ShoppingList<'_> {
+    pub fn total_price(&self) -> f64 {
+        self.product_price + self.penalty
+    }
+}
+
+/// Finds the combination of stores that minimizes the total cost of the products
+/// plus the penalty for visiting each store.
+///
+/// Returns `None` if the report doesn't contain any stores.
+pub fn optimize<'a>(report: &'a Report, penalty: &Penalty) -> Option<ShoppingList<'a>> {
+    let stores = report.stores.keys().copied().sorted_by_key(Store::id);
+
+    stores
+        .powerset()
+        .filter(|stores| !stores.is_empty())
+        .filter_map(|stores| {
+            // stores can be missing ingredients, skip combinations that don't have them all
+            let picks = report
+                .ingredients
+                .values()
+                .map(|ingredient| {
+                    let (store, product) = stores
+                        .iter()
+                        .filter_map(|store| {
+                            let product = report.stores[store].get(&ingredient.id)?;
+                            Some((*store, product))
+                        })
+                        .min_by(|(_, a), (_, b)| a.price.total_cmp(&b.price))?;
+
+                    Some((store, ingredient, product))
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            // only count the stores we actually buy something from
+            let used_stores = picks
+                .iter()
+                .map(|(store, _, _)| *store)
+                .unique()
+                .collect_vec();
+
+            let product_price: f64 = picks.iter().map(|(_, _, product)| product.price).sum();
+            let penalty = penalty.cost(&used_stores);
+
+            Some((picks, product_price, penalty))
+        })
+        .min_by(|(_, a_price, a_penalty), (_, b_price, b_penalty)| {
+            (a_price + a_penalty).total_cmp(&(b_price + b_penalty))
+        })
+        .map(|(picks, product_price, penalty)| {
+            let stores = picks
+                .into_iter()
+                .into_group_map_by(|(store, _, _)| *store)
+                .into_iter()
+                .map(|(store, picks)| {
+                    let products = picks
+                        .into_iter()
+                        .map(|(_, ingredient, product)| (ingredient, product))
+                        .sorted_by(|(_, a), (_, b)| a.price.total_cmp(&b.price).reverse())
+                        .collect_vec();
+
+                    (store, products)
+                })
+                .sorted_by(|(a_store, a), (b_store, b)| {
+                    b.len()
+                        .cmp(&a.len())
+                        .then_with(|| a_store.id().cmp(b_store.id()))
+                })
+                .collect();
+
+            ShoppingList {
+                stores,
+                product_price,
+                penalty,
+            }
+        })
+}
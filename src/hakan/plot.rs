@@ -1,24 +1,330 @@
 use std::{
     collections::HashMap,
-    path::{Path, PathBuf},
+    fmt::Debug,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+    sync::{Arc, Mutex},
 };
 
-use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeDelta, Utc};
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
 use itertools::Itertools;
 use plotters::{
+    coord::{types::RangedCoordf64, Shift},
     prelude::*,
-    style::full_palette::{ORANGE, PINK, PURPLE},
+    style::text_anchor::{HPos, Pos, VPos},
 };
+use serenity::all::CreateAttachment;
 use uuid::Uuid;
 
 use crate::AppState;
 
-pub async fn create_total(state: &AppState, keep: bool) -> Result<String> {
-    let path = get_path().await?;
-    let reports = super::db::reports(state).await?;
+use super::{
+    db::{Extremes, PlotEvent, Range, SeriesLabel},
+    forecast::Forecast,
+    Report, Store,
+};
+
+/// How a plot looks and which file format it's drawn to.
+#[derive(Debug, Clone)]
+pub struct PlotStyle {
+    pub theme: Theme,
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    pub font: String,
+    pub font_size: u32,
+    pub line_width: u32,
+}
+
+impl Default for PlotStyle {
+    fn default() -> Self {
+        Self {
+            theme: Theme::Light,
+            format: ImageFormat::Png,
+            width: 800,
+            height: 600,
+            font: "sans-serif".to_string(),
+            font_size: 20,
+            line_width: 1,
+        }
+    }
+}
 
-    let last = reports.iter().last().unwrap();
+/// Plots smaller than this don't leave room for the labels.
+pub const MIN_SIZE: (u32, u32) = (400, 300);
+/// Discord scales larger images down anyway.
+pub const MAX_SIZE: (u32, u32) = (2000, 1500);
+/// Font sizes that still leave room for the charts at [`MIN_SIZE`].
+const FONT_SIZES: (u32, u32) = (8, 48);
+
+impl PlotStyle {
+    /// The default style, with each setting taken from its environment variable if it's set:
+    /// `PLOT_THEME`, `PLOT_WIDTH`, `PLOT_HEIGHT`, `PLOT_FONT`, `PLOT_FONT_SIZE` and `PLOT_LINE_WIDTH`.
+    pub fn configured() -> Self {
+        let default = Self::default();
+
+        let theme = env("PLOT_THEME")
+            .and_then(|id| Theme::from_id(&id))
+            .unwrap_or(default.theme);
+        let width = env("PLOT_WIDTH")
+            .and_then(|width| width.parse().ok())
+            .unwrap_or(default.width);
+        let height = env("PLOT_HEIGHT")
+            .and_then(|height| height.parse().ok())
+            .unwrap_or(default.height);
+
+        Self {
+            theme,
+            font: env("PLOT_FONT").unwrap_or(default.font),
+            font_size: env("PLOT_FONT_SIZE")
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(default.font_size)
+                .clamp(FONT_SIZES.0, FONT_SIZES.1),
+            line_width: env("PLOT_LINE_WIDTH")
+                .and_then(|width| width.parse().ok())
+                .unwrap_or(default.line_width),
+            ..default
+        }
+        .with_size(width, height)
+    }
+
+    /// Sets the size of the plot, clamped to [`MIN_SIZE`] and [`MAX_SIZE`].
+    pub fn with_size(self, width: u32, height: u32) -> Self {
+        Self {
+            width: width.clamp(MIN_SIZE.0, MAX_SIZE.0),
+            height: height.clamp(MIN_SIZE.1, MAX_SIZE.1),
+            ..self
+        }
+    }
+
+    fn text(&self, size: u32) -> TextStyle<'_> {
+        (self.font.as_str(), size)
+            .into_font()
+            .color(&self.theme.foreground())
+    }
+}
+
+fn env(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|value| !value.is_empty())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    /// Matches Discord's dark mode.
+    Dark,
+}
+
+impl Theme {
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "ljust" => Some(Theme::Light),
+            "mörkt" => Some(Theme::Dark),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Theme::Light => "ljust",
+            Theme::Dark => "mörkt",
+        }
+    }
+
+    fn background(&self) -> RGBColor {
+        match self {
+            Theme::Light => WHITE,
+            Theme::Dark => RGBColor(0x31, 0x33, 0x38),
+        }
+    }
+
+    fn foreground(&self) -> RGBColor {
+        match self {
+            Theme::Light => BLACK,
+            Theme::Dark => RGBColor(0xdb, 0xde, 0xe1),
+        }
+    }
+
+    /// Swaps out series colours that would disappear against the background.
+    fn adapt(&self, color: RGBColor) -> RGBColor {
+        match self {
+            Theme::Dark if color == BLACK => self.foreground(),
+            _ => color,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    /// Discord can't show these in embeds, so they have to be linked instead.
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+/// A rendered plot, either uploaded to storage or to be attached to the message showing it.
+#[derive(Clone)]
+pub struct Plot {
+    pub file_name: String,
+    pub data: Vec<u8>,
+    /// Where the plot was uploaded, if there's storage configured.
+    pub url: Option<String>,
+}
+
+impl Plot {
+    /// The url to use in embeds, which points to the attachment if the plot wasn't uploaded.
+    pub fn embed_url(&self) -> String {
+        match &self.url {
+            Some(url) => url.clone(),
+            None => format!("attachment://{}", self.file_name),
+        }
+    }
+
+    /// The file to send along with the message, if the plot wasn't uploaded.
+    pub fn attachment(&self) -> Option<CreateAttachment> {
+        match self.url {
+            Some(_) => None,
+            None => Some(CreateAttachment::bytes(
+                self.data.clone(),
+                self.file_name.clone(),
+            )),
+        }
+    }
+}
+
+/// Rendered plots by [`cache_key`], so that asking for the same plot again doesn't
+/// render and upload a new one. Cleared whenever the data behind the plots changes.
+#[derive(Debug, Clone, Default)]
+pub struct PlotCache(Arc<Mutex<HashMap<u64, Plot>>>);
+
+/// The cache is cleared instead of evicting single plots once it's this big.
+const MAX_CACHED_PLOTS: usize = 128;
+
+impl PlotCache {
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+
+    fn get(&self, key: u64) -> Option<Plot> {
+        self.0.lock().unwrap().get(&key).cloned()
+    }
+
+    fn insert(&self, key: u64, plot: &Plot) {
+        let mut plots = self.0.lock().unwrap();
+
+        if plots.len() >= MAX_CACHED_PLOTS {
+            plots.clear();
+        }
+
+        plots.insert(key, plot.clone());
+    }
+}
+
+impl Debug for Plot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Plot")
+            .field("file_name", &self.file_name)
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Hashes everything that decides what a plot looks like: its kind and parameters,
+/// the latest report and the style.
+async fn cache_key(
+    kind: &str,
+    params: impl Debug,
+    style: &PlotStyle,
+    state: &AppState,
+) -> Result<u64> {
+    let report_id = sqlx::query_scalar!("SELECT MAX(id) FROM reports")
+        .fetch_one(&state.db)
+        .await?;
+
+    let mut hasher = DefaultHasher::new();
+    (kind, format!("{params:?}"), report_id, format!("{style:?}")).hash(&mut hasher);
+
+    Ok(hasher.finish())
+}
+
+/// The days `range` covers, since relative ranges like the last week change by the second.
+fn range_days(range: &Range) -> (Option<NaiveDate>, Option<NaiveDate>) {
+    (
+        range.from.map(|from| from.date()),
+        range.to.map(|to| to.date()),
+    )
+}
+
+/// Calls a `draw_*` function with an in-memory drawing area in the format of `style`
+/// and returns the encoded image.
+macro_rules! render {
+    ($style:expr, $size:expr, $draw:ident($($arg:expr),* $(,)?)) => {{
+        let (width, height) = $size;
+
+        match $style.format {
+            ImageFormat::Png => {
+                let mut buffer = vec![0; width as usize * height as usize * 3];
+                $draw(BitMapBackend::with_buffer(&mut buffer, (width, height)).into_drawing_area(), $($arg),*)?;
+                encode_png(&buffer, width, height)?
+            }
+            ImageFormat::Svg => {
+                let mut svg = String::new();
+                $draw(SVGBackend::with_string(&mut svg, (width, height)).into_drawing_area(), $($arg),*)?;
+                svg.into_bytes()
+            }
+        }
+    }};
+}
+
+pub async fn create_total(
+    state: &AppState,
+    range: &Range,
+    forecast: Option<&Forecast>,
+    style: &PlotStyle,
+    keep: bool,
+) -> Result<Plot> {
+    // the forecast starts today, so it doesn't belong on ranges that end before that
+    let forecast = forecast.filter(|_| range.to.is_none_or(|to| to > Utc::now().naive_utc()));
+
+    let key = cache_key(
+        "total",
+        (
+            range_days(range),
+            forecast.map(|forecast| forecast.points.len()),
+        ),
+        style,
+        state,
+    )
+    .await?;
+
+    if let Some(plot) = cached(key, keep, state) {
+        return Ok(plot);
+    }
+
+    let resolution = super::db::resolution(range, state).await?;
+    let reports = super::db::reports(range, resolution, state).await?;
+
+    let last = reports
+        .iter()
+        .last()
+        .context("no reports in the given range")?;
 
     let color = if reports[0].price > last.price {
         GREEN
@@ -31,13 +337,37 @@ pub async fn create_total(state: &AppState, keep: bool) -> Result<String> {
         .map(|report| (report.created_at.and_utc(), report.price))
         .collect_vec();
 
-    draw(vec![(color.into(), None, series)], &path, false)?;
-    upload(path, state, keep).await
+    let events = super::db::plot_events(range, state).await?;
+    let extremes = super::db::total_extremes(state).await?;
+    let overlay = Overlay {
+        forecast,
+        events: &events,
+        extremes: extremes.as_ref(),
+        annotate: true,
+    };
+
+    let data = render!(
+        style,
+        (style.width, style.height),
+        draw(vec![(color.into(), None, series)], &overlay, style, false)
+    );
+    finish(data, Some(key), style, state, keep).await
 }
 
-pub async fn create_by_store(state: &AppState, keep: bool) -> Result<String> {
-    let path = get_path().await?;
-    let reports = super::db::reports_by_store(state).await?;
+pub async fn create_by_store(
+    state: &AppState,
+    range: &Range,
+    style: &PlotStyle,
+    keep: bool,
+) -> Result<Plot> {
+    let key = cache_key("by_store", range_days(range), style, state).await?;
+
+    if let Some(plot) = cached(key, keep, state) {
+        return Ok(plot);
+    }
+
+    let resolution = super::db::resolution(range, state).await?;
+    let reports = super::db::reports_by_store(range, resolution, state).await?;
 
     let mut stores = HashMap::new();
 
@@ -48,29 +378,45 @@ pub async fn create_by_store(state: &AppState, keep: bool) -> Result<String> {
             .push((report.created_at.and_utc(), report.price));
     }
 
+    let legend = Legend::stores(stores.keys(), state).await?;
+
     let serieses = stores
         .into_iter()
         .map(|(store, values)| {
-            let color = match store.as_str() {
-                "coop" => GREEN,
-                "ica" => RED,
-                "hemkop" => PINK,
-                "willys" => BLACK,
-                "mathem" => BLUE,
-                _ => BLACK,
-            };
-
-            (color.into(), Some(store), values)
+            let color = legend.color(&store, style.theme);
+            (color.into(), Some(legend.label(&store)), values)
         })
         .collect();
 
-    draw(serieses, &path, true)?;
-    upload(path, state, keep).await
+    let events = super::db::plot_events(range, state).await?;
+    let overlay = Overlay {
+        events: &events,
+        annotate: true,
+        ..Default::default()
+    };
+
+    let data = render!(
+        style,
+        (style.width, style.height),
+        draw(serieses, &overlay, style, true)
+    );
+    finish(data, Some(key), style, state, keep).await
 }
 
-pub async fn create_by_ingredient(state: &AppState, keep: bool) -> Result<String> {
-    let path = get_path().await?;
-    let reports = super::db::reports_by_ingredient(state).await?;
+pub async fn create_by_ingredient(
+    state: &AppState,
+    range: &Range,
+    style: &PlotStyle,
+    keep: bool,
+) -> Result<Plot> {
+    let key = cache_key("by_ingredient", range_days(range), style, state).await?;
+
+    if let Some(plot) = cached(key, keep, state) {
+        return Ok(plot);
+    }
+
+    let resolution = super::db::resolution(range, state).await?;
+    let reports = super::db::reports_by_ingredient(range, resolution, state).await?;
 
     let mut ingredients = HashMap::new();
 
@@ -81,99 +427,1012 @@ pub async fn create_by_ingredient(state: &AppState, keep: bool) -> Result<String
             .push((report.created_at.and_utc(), report.price));
     }
 
+    let legend = Legend::ingredients(ingredients.keys(), state).await?;
+
     let serieses = ingredients
         .into_iter()
         .map(|(ingredient, values)| {
-            let color = match ingredient.as_str() {
-                "Vetemjöl" => RED,
-                "Kakao" => ORANGE,
-                "Ägg" => GREEN,
-                "Smör" => BLUE,
-                "Strösocker" => PURPLE,
-                _ => BLACK,
+            let color = legend.color(&ingredient, style.theme);
+            (color.into(), Some(legend.label(&ingredient)), values)
+        })
+        .collect();
+
+    let events = super::db::plot_events(range, state).await?;
+    let overlay = Overlay {
+        events: &events,
+        annotate: true,
+        ..Default::default()
+    };
+
+    let data = render!(
+        style,
+        (style.width, style.height),
+        draw(serieses, &overlay, style, true)
+    );
+    finish(data, Some(key), style, state, keep).await
+}
+
+/// The cheapest price of each ingredient stacked on top of each other, so that the top
+/// follows the total. With `share`, each ingredient is shown as a percentage of the total instead.
+pub async fn create_contribution(
+    state: &AppState,
+    range: &Range,
+    share: bool,
+    style: &PlotStyle,
+    keep: bool,
+) -> Result<Plot> {
+    let key = cache_key("contribution", (range_days(range), share), style, state).await?;
+
+    if let Some(plot) = cached(key, keep, state) {
+        return Ok(plot);
+    }
+
+    let resolution = super::db::resolution(range, state).await?;
+    let reports = super::db::reports_by_ingredient(range, resolution, state).await?;
+
+    let dates = reports
+        .iter()
+        .map(|report| report.created_at.and_utc())
+        .unique()
+        .sorted()
+        .collect_vec();
+
+    if dates.is_empty() {
+        bail!("no reports in the given range");
+    }
+
+    let mut prices: HashMap<String, HashMap<DateTime<Utc>, f64>> = HashMap::new();
+
+    for report in &reports {
+        prices
+            .entry(report.ingredient_name.clone())
+            .or_default()
+            .insert(report.created_at.and_utc(), report.price);
+    }
+
+    let totals = dates
+        .iter()
+        .map(|date| {
+            prices
+                .values()
+                .filter_map(|values| values.get(date))
+                .sum::<f64>()
+        })
+        .collect_vec();
+
+    let legend = Legend::ingredients(prices.keys(), state).await?;
+
+    let layers = prices
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(ingredient, values)| {
+            let values = dates
+                .iter()
+                .zip(&totals)
+                .map(|(date, total)| {
+                    let price = values.get(date).copied().unwrap_or(0.0);
+                    let value = if share { price / total * 100.0 } else { price };
+
+                    (*date, value)
+                })
+                .collect_vec();
+
+            (
+                legend.color(&ingredient, style.theme),
+                legend.label(&ingredient),
+                values,
+            )
+        })
+        .collect_vec();
+
+    let data = render!(
+        style,
+        (style.width, style.height),
+        draw_stacked(layers, share, style)
+    );
+    finish(data, Some(key), style, state, keep).await
+}
+
+/// One panel per ingredient with a line per store, or a single panel if `ingredient` is given.
+pub async fn create_by_ingredient_and_store(
+    state: &AppState,
+    range: &Range,
+    ingredient: Option<&str>,
+    style: &PlotStyle,
+    keep: bool,
+) -> Result<Plot> {
+    let key = cache_key(
+        "by_ingredient_and_store",
+        (range_days(range), ingredient),
+        style,
+        state,
+    )
+    .await?;
+
+    if let Some(plot) = cached(key, keep, state) {
+        return Ok(plot);
+    }
+
+    let resolution = super::db::resolution(range, state).await?;
+    let reports =
+        super::db::reports_by_ingredient_and_store(range, resolution, ingredient, state).await?;
+
+    let mut ingredients: HashMap<String, HashMap<String, Vec<_>>> = HashMap::new();
+
+    for report in reports {
+        ingredients
+            .entry(report.ingredient_name)
+            .or_default()
+            .entry(report.store)
+            .or_default()
+            .push((report.created_at.and_utc(), report.price));
+    }
+
+    if ingredients.is_empty() {
+        bail!("no reports in the given range");
+    }
+
+    let ingredient_legend = Legend::ingredients(ingredients.keys(), state).await?;
+    let store_legend =
+        Legend::stores(ingredients.values().flat_map(|stores| stores.keys()), state).await?;
+
+    let panels = ingredients
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(ingredient, stores)| {
+            let serieses = stores
+                .into_iter()
+                .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                .map(|(store, values)| {
+                    let color = store_legend.color(&store, style.theme);
+                    (color.into(), Some(store_legend.label(&store)), values)
+                })
+                .collect();
+
+            (ingredient_legend.label(&ingredient), serieses)
+        })
+        .collect_vec();
+
+    let cols = (panels.len() as f64).sqrt().ceil() as usize;
+    let rows = panels.len().div_ceil(cols);
+
+    // a single panel gets the whole plot, otherwise each panel is a quarter of it,
+    // shrunk if the grid would be larger than the largest plot
+    let size = if panels.len() == 1 {
+        (style.width, style.height)
+    } else {
+        (
+            (style.width / 2 * cols as u32).min(MAX_SIZE.0),
+            (style.height / 2 * rows as u32).min(MAX_SIZE.1),
+        )
+    };
+
+    let data = render!(style, size, draw_panels(panels, (rows, cols), style));
+    finish(data, Some(key), style, state, keep).await
+}
+
+/// Compares the stores in `report` with a bar per store, either stacked by ingredient
+/// or as the whole basket. Anomalous products are left out, and stores that are missing
+/// any ingredient because of it are marked with an asterisk.
+pub async fn create_store_comparison(
+    state: &AppState,
+    report: &Report,
+    by_ingredient: bool,
+    style: &PlotStyle,
+    keep: bool,
+) -> Result<Plot> {
+    let ingredients = report
+        .ingredients
+        .values()
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .collect_vec();
+
+    let ingredient_legend =
+        Legend::ingredients(ingredients.iter().map(|ingredient| &ingredient.name), state).await?;
+    let store_legend = Legend::stores(Store::ALL.iter().map(|store| store.id()), state).await?;
+
+    let bars = Store::ALL
+        .into_iter()
+        .filter_map(|store| {
+            let products = report.stores.get(&store)?;
+
+            let segments = ingredients
+                .iter()
+                .filter(|ingredient| !report.is_anomaly(store, ingredient))
+                .filter_map(|ingredient| {
+                    let product = products.get(&ingredient.id)?;
+                    Some((ingredient.name.clone(), product.price))
+                })
+                .collect_vec();
+
+            // stores that are missing ingredients would look cheaper than they are
+            let label = if segments.len() < ingredients.len() {
+                format!("{}*", store_legend.label(store.id()))
+            } else {
+                store_legend.label(store.id())
             };
 
-            (color.into(), Some(ingredient), values)
+            let segments = if by_ingredient {
+                segments
+                    .into_iter()
+                    .map(|(name, price)| {
+                        let color = ingredient_legend.color(&name, style.theme);
+                        (color, ingredient_legend.label(&name), price)
+                    })
+                    .collect()
+            } else {
+                let total = segments.iter().map(|(_, price)| price).sum();
+                let color = store_legend.color(store.id(), style.theme);
+                vec![(color, store_legend.label(store.id()), total)]
+            };
+
+            Some((label, segments))
+        })
+        .collect_vec();
+
+    if bars.is_empty() {
+        bail!("the report has no stores");
+    }
+
+    let note = bars
+        .iter()
+        .any(|(label, _)| label.ends_with('*'))
+        .then_some("* saknar ingredienser");
+
+    let data = render!(
+        style,
+        (style.width, style.height),
+        draw_bars(bars, by_ingredient, note, style)
+    );
+    finish(data, None, style, state, keep).await
+}
+
+pub async fn create_inflation(
+    state: &AppState,
+    comparison: &super::cpi::Comparison,
+    style: &PlotStyle,
+    keep: bool,
+) -> Result<Plot> {
+    let serieses = vec![
+        (
+            RED.into(),
+            Some("Håkan".to_string()),
+            comparison.hakan.clone(),
+        ),
+        (
+            BLUE.into(),
+            Some("KPI livsmedel".to_string()),
+            comparison.cpi.clone(),
+        ),
+    ];
+
+    let events = super::db::plot_events(&Range::default(), state).await?;
+    let overlay = Overlay {
+        events: &events,
+        annotate: true,
+        ..Default::default()
+    };
+
+    let data = render!(
+        style,
+        (style.width, style.height),
+        draw(serieses, &overlay, style, true)
+    );
+    finish(data, None, style, state, keep).await
+}
+
+/// A GitHub style calendar of `year`, with each day coloured by how much the total
+/// changed since the report before it.
+pub async fn create_calendar(
+    state: &AppState,
+    year: i32,
+    style: &PlotStyle,
+    keep: bool,
+) -> Result<Plot> {
+    let key = cache_key("calendar", year, style, state).await?;
+
+    if let Some(plot) = cached(key, keep, state) {
+        return Ok(plot);
+    }
+
+    let first_day = NaiveDate::from_ymd_opt(year, 1, 1).context("invalid year")?;
+    let last_day = NaiveDate::from_ymd_opt(year, 12, 31).context("invalid year")?;
+
+    // start a bit earlier so that the first days of the year have something to compare to
+    let range = Range {
+        from: Some((first_day - TimeDelta::days(14)).and_time(NaiveTime::MIN)),
+        to: Some((last_day + TimeDelta::days(1)).and_time(NaiveTime::MIN)),
+    };
+    let reports = super::db::reports(&range, super::db::Resolution::Day, state).await?;
+
+    let changes: HashMap<NaiveDate, f64> = reports
+        .iter()
+        .tuple_windows()
+        .filter(|(_, report)| report.created_at.year() == year)
+        .map(|(last, report)| {
+            let change = (report.price - last.price) / last.price * 100.0;
+            (report.created_at.date(), change)
         })
         .collect();
 
-    draw(serieses, &path, true)?;
-    upload(path, state, keep).await
+    if changes.is_empty() {
+        bail!("no reports in {year}");
+    }
+
+    let days = first_day
+        .iter_days()
+        .take_while(|date| *date <= last_day)
+        .map(|date| (date, changes.get(&date).copied()))
+        .collect_vec();
+
+    let label_size = style.font_size * 6 / 10;
+    let cell = (style.width.saturating_sub(label_size * 3 + 20) / 53).max(CALENDAR_MIN_CELL);
+    let size = (style.width, label_size * 2 + cell * 7 + label_size * 4);
+
+    let data = render!(style, size, draw_calendar(days, cell, label_size, style));
+    finish(data, Some(key), style, state, keep).await
+}
+
+fn encode_png(buffer: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    PngEncoder::new(&mut data).write_image(buffer, width, height, ColorType::Rgb8)?;
+    Ok(data)
+}
+
+/// Parses a `#rrggbb` colour.
+pub fn parse_color(color: &str) -> Option<RGBColor> {
+    let hex = color.strip_prefix('#')?;
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(RGBColor(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Legend labels and colours of the series in a plot, from the `stores` and `ingredients` tables.
+///
+/// Series without a colour of their own get one from a palette, in the order of their keys,
+/// so that they don't all end up the same colour.
+struct Legend {
+    labels: HashMap<String, String>,
+    colors: HashMap<String, RGBColor>,
 }
 
-async fn get_path() -> Result<PathBuf> {
-    let uuid = Uuid::new_v4().to_string();
-    let dir = std::env::var("PLOTS_DIRECTORY").context("PLOTS_DIRECTORY must be set")?;
-    tokio::fs::create_dir_all(&dir).await?;
+impl Legend {
+    async fn stores(
+        keys: impl IntoIterator<Item = impl AsRef<str>>,
+        state: &AppState,
+    ) -> Result<Self> {
+        Ok(Self::new(super::db::store_labels(state).await?, keys))
+    }
+
+    async fn ingredients(
+        keys: impl IntoIterator<Item = impl AsRef<str>>,
+        state: &AppState,
+    ) -> Result<Self> {
+        Ok(Self::new(super::db::ingredient_labels(state).await?, keys))
+    }
+
+    /// `keys` are the series in the plot, which might not all be in `entries`.
+    fn new(entries: Vec<SeriesLabel>, keys: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let mut labels = HashMap::new();
+        let mut colors = HashMap::new();
+        let mut uncolored = Vec::new();
+
+        for entry in entries.into_iter().sorted_by(|a, b| a.key.cmp(&b.key)) {
+            match entry.color.as_deref().and_then(parse_color) {
+                Some(color) => {
+                    colors.insert(entry.key.clone(), color);
+                }
+                None => uncolored.push(entry.key.clone()),
+            }
+
+            labels.insert(entry.key, entry.label);
+        }
 
-    let path = PathBuf::from(dir).join(uuid).with_extension("png");
-    Ok(path)
+        // keys missing from the table go last, so that they don't shift the others' colours
+        let missing = keys
+            .into_iter()
+            .map(|key| key.as_ref().to_string())
+            .filter(|key| !labels.contains_key(key))
+            .sorted()
+            .dedup()
+            .collect_vec();
+
+        let used = colors.values().copied().collect_vec();
+        let mut palette = (0..)
+            .map(|i| {
+                let (r, g, b) = Palette99::pick(i).rgb();
+                RGBColor(r, g, b)
+            })
+            .filter(|color| !used.contains(color));
+
+        for key in uncolored.into_iter().chain(missing) {
+            colors.insert(key, palette.next().unwrap());
+        }
+
+        Self { labels, colors }
+    }
+
+    /// Falls back to the store's display name, or else the key itself.
+    fn label(&self, key: &str) -> String {
+        match self.labels.get(key) {
+            Some(label) => label.clone(),
+            None => Store::from_id(key)
+                .map(|store| store.to_string())
+                .unwrap_or_else(|| key.to_string()),
+        }
+    }
+
+    fn color(&self, key: &str, theme: Theme) -> RGBColor {
+        theme.adapt(self.colors.get(key).copied().unwrap_or(BLACK))
+    }
 }
 
-fn draw(
-    serieses: Vec<(RGBAColor, Option<String>, Vec<(DateTime<Utc>, f64)>)>,
-    path: &Path,
+type Series = (RGBAColor, Option<String>, Vec<(DateTime<Utc>, f64)>);
+/// A label and the `(color, label, value)` segments stacked in it.
+type Bar = (String, Vec<(RGBColor, String, f64)>);
+/// A `(color, label, values)` area in a stacked chart.
+type Layer = (RGBColor, String, Vec<(DateTime<Utc>, f64)>);
+
+/// What's drawn in a chart besides the lines themselves.
+#[derive(Default)]
+struct Overlay<'a> {
+    forecast: Option<&'a Forecast>,
+    /// Marked with vertical lines, if they're within the chart.
+    events: &'a [PlotEvent],
+    /// The all-time highest and lowest values, marked where they are if they're
+    /// within the chart and as horizontal reference lines otherwise.
+    extremes: Option<&'a Extremes>,
+    /// Labels the latest value of each series.
+    annotate: bool,
+}
+
+fn draw<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    serieses: Vec<Series>,
+    overlay: &Overlay,
+    style: &PlotStyle,
     draw_labels: bool,
-) -> Result<()> {
-    let root = BitMapBackend::new(path, (800, 600)).into_drawing_area();
+) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&style.theme.background())?;
+    draw_chart(
+        &root,
+        None,
+        serieses,
+        overlay,
+        draw_labels,
+        style,
+        style.font_size,
+    )?;
+    root.present()?;
+
+    Ok(())
+}
+
+/// Draws a `(rows, cols)` grid of charts, one for each `(caption, serieses)` panel.
+fn draw_panels<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    panels: Vec<(String, Vec<Series>)>,
+    grid: (usize, usize),
+    style: &PlotStyle,
+) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    let font_size = if panels.len() == 1 {
+        style.font_size
+    } else {
+        style.font_size * 7 / 10
+    };
 
-    root.fill(&WHITE)?;
+    root.fill(&style.theme.background())?;
 
+    for (i, (area, (caption, serieses))) in root.split_evenly(grid).iter().zip(panels).enumerate() {
+        // every panel has the same stores, so one legend is enough
+        draw_chart(
+            area,
+            Some(&caption),
+            serieses,
+            &Overlay::default(),
+            i == 0,
+            style,
+            font_size,
+        )?;
+    }
+
+    root.present()?;
+
+    Ok(())
+}
+
+fn draw_chart<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    caption: Option<&str>,
+    serieses: Vec<Series>,
+    overlay: &Overlay,
+    draw_labels: bool,
+    style: &PlotStyle,
+    font_size: u32,
+) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
     let start_date = serieses
         .iter()
         .filter_map(|(_, _, series)| series.iter().map(|(date, _)| date).min())
         .min()
-        .map(|date| *date)
+        .copied()
         .unwrap_or_default();
 
-    let end_date = serieses
+    let mut end_date = serieses
         .iter()
         .filter_map(|(_, _, series)| series.iter().map(|(date, _)| date).max())
         .max()
-        .map(|date| *date)
+        .copied()
         .unwrap_or_default();
 
-    let min_price = min_float_iter(
+    let mut min_price = min_float_iter(
         serieses
             .iter()
             .map(|(_, _, series)| min_float_iter(series.iter().map(|(_, value)| *value))),
     );
 
-    let max_price = max_float_iter(
+    let mut max_price = max_float_iter(
         serieses
             .iter()
             .map(|(_, _, series)| max_float_iter(series.iter().map(|(_, value)| *value))),
     );
 
-    let mut chart = ChartBuilder::on(&root)
-        .set_label_area_size(LabelAreaPosition::Left, 60)
-        .set_label_area_size(LabelAreaPosition::Bottom, 60)
-        .margin(10)
-        .build_cartesian_2d(start_date..end_date, min_price..max_price)?;
+    if let Some(forecast) = overlay.forecast {
+        if let Some(last) = forecast.points.last() {
+            end_date = end_date.max(last.date);
+        }
+
+        min_price = min_price.min(min_float_iter(
+            forecast.points.iter().map(|point| point.lower),
+        ));
+        max_price = max_price.max(max_float_iter(
+            forecast.points.iter().map(|point| point.upper),
+        ));
+    }
+
+    if let Some(extremes) = overlay.extremes {
+        min_price = min_price.min(extremes.low.1);
+        max_price = max_price.max(extremes.high.1);
+    }
+
+    if overlay.annotate {
+        // leave room for the labels above and below the lines
+        let padding = (max_price - min_price) * 0.08;
+        min_price -= padding;
+        max_price += padding;
+    }
+
+    let mut builder = ChartBuilder::on(area);
+
+    if let Some(caption) = caption {
+        builder.caption(caption, style.text(font_size + 4));
+    }
+
+    builder
+        .set_label_area_size(LabelAreaPosition::Left, font_size * 3)
+        .set_label_area_size(LabelAreaPosition::Bottom, font_size * 3)
+        .margin(10);
+
+    if overlay.annotate {
+        // room for the latest values to the right of the lines
+        builder.margin_right(font_size * 3);
+    }
+
+    let mut chart = builder.build_cartesian_2d(start_date..end_date, min_price..max_price)?;
+
+    // roughly one date label per 160 pixels, which is 5 for a full size plot
+    let x_labels = (area.dim_in_pixel().0 / 160).max(2) as usize;
 
     chart
         .configure_mesh()
-        .x_labels(5)
+        .axis_style(style.theme.foreground())
+        .bold_line_style(style.theme.foreground().mix(0.2))
+        .light_line_style(style.theme.foreground().mix(0.08))
+        .x_labels(x_labels)
         .y_labels(5)
-        .label_style(("sans-serif", 20))
+        .label_style(style.text(font_size))
         .x_label_formatter(&|date| date.format("%Y-%m-%d").to_string())
         .draw()?;
 
+    for event in overlay.events {
+        let date = event.date.and_time(NaiveTime::MIN).and_utc();
+
+        if date < start_date || date > end_date {
+            continue;
+        }
+
+        let line_color = style.theme.foreground().mix(0.5);
+
+        chart.draw_series(DashedLineSeries::new(
+            [(date, min_price), (date, max_price)],
+            6,
+            4,
+            line_color.stroke_width(style.line_width),
+        ))?;
+
+        let text_style = style
+            .text(font_size * 7 / 10)
+            .pos(Pos::new(HPos::Left, VPos::Top));
+
+        chart.draw_series(std::iter::once(
+            EmptyElement::at((date, max_price))
+                + Text::new(event.label.clone(), (4, 4), text_style),
+        ))?;
+    }
+
+    if overlay.annotate {
+        annotate(&mut chart, &serieses, overlay.extremes, style, font_size)?;
+    }
+
     for (color, label, values) in serieses {
+        let line_style = color.stroke_width(style.line_width);
+
         let series = chart
-            .draw_series(LineSeries::new(values, color))?
-            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+            .draw_series(LineSeries::new(values, line_style))?
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], line_style));
 
         if let Some(label) = label {
             series.label(label);
         }
     }
 
+    if let Some(forecast) = overlay.forecast {
+        let band = forecast
+            .points
+            .iter()
+            .map(|point| (point.date, point.upper))
+            .chain(
+                forecast
+                    .points
+                    .iter()
+                    .rev()
+                    .map(|point| (point.date, point.lower)),
+            )
+            .collect_vec();
+
+        chart.draw_series(std::iter::once(Polygon::new(band, BLUE.mix(0.15))))?;
+
+        chart.draw_series(DashedLineSeries::new(
+            forecast
+                .points
+                .iter()
+                .map(|point| (point.date, point.price)),
+            10,
+            6,
+            BLUE.stroke_width(style.line_width + 1),
+        ))?;
+    }
+
     if draw_labels {
         chart
             .configure_series_labels()
-            .label_font(("sans-serif", 20))
-            .border_style(BLACK)
+            .label_font(style.text(font_size))
+            .background_style(style.theme.background().mix(0.8))
+            .border_style(style.theme.foreground())
+            .draw()?;
+    }
+
+    Ok(())
+}
+
+/// Labels the latest value of each series to the right of the chart, and marks the
+/// all-time `extremes` if they're given.
+fn annotate<DB: DrawingBackend>(
+    chart: &mut ChartContext<DB, Cartesian2d<RangedDateTime<DateTime<Utc>>, RangedCoordf64>>,
+    serieses: &[Series],
+    extremes: Option<&Extremes>,
+    style: &PlotStyle,
+    font_size: u32,
+) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    let font_size = font_size * 8 / 10;
+
+    let latest = serieses
+        .iter()
+        .filter_map(|(color, _, values)| {
+            let &(date, value) = values.last()?;
+            let y = chart.backend_coord(&(date, value)).1;
+            Some((*color, date, value, y))
+        })
+        .sorted_by_key(|(_, _, _, y)| *y)
+        .collect_vec();
+
+    // push the labels apart so that stores with similar prices don't overlap
+    let mut label_y = latest.iter().map(|(_, _, _, y)| *y).collect_vec();
+    for i in 1..label_y.len() {
+        label_y[i] = label_y[i].max(label_y[i - 1] + font_size as i32);
+    }
+
+    for ((color, date, value, y), label_y) in latest.into_iter().zip(label_y) {
+        chart.draw_series(std::iter::once(
+            EmptyElement::at((date, value))
+                + Circle::new((0, 0), 3, color.filled())
+                + Text::new(
+                    format!("{value:0.1}"),
+                    (6, label_y - y),
+                    style
+                        .text(font_size)
+                        .color(&color)
+                        .pos(Pos::new(HPos::Left, VPos::Center)),
+                ),
+        ))?;
+    }
+
+    let Some(extremes) = extremes else {
+        return Ok(());
+    };
+
+    let color = match serieses {
+        [(color, _, _)] => *color,
+        _ => style.theme.foreground().into(),
+    };
+
+    let x_range = chart.x_range();
+    let (x_pixels, _) = chart.plotting_area().get_pixel_range();
+
+    for ((date, value), label, offset, vpos) in [
+        (extremes.high, "Högst", -8, VPos::Bottom),
+        (extremes.low, "Lägst", 8, VPos::Top),
+    ] {
+        let date = date.and_utc();
+
+        if date < x_range.start || date > x_range.end {
+            // outside the chart, so show it as a reference line instead
+            chart.draw_series(DashedLineSeries::new(
+                [(x_range.start, value), (x_range.end, value)],
+                6,
+                4,
+                color.mix(0.6).stroke_width(style.line_width),
+            ))?;
+
+            chart.draw_series(std::iter::once(
+                EmptyElement::at((x_range.start, value))
+                    + Text::new(
+                        format!("{label} {value:0.1} ({})", date.format("%Y-%m-%d")),
+                        (4, offset / 2),
+                        style
+                            .text(font_size)
+                            .color(&color)
+                            .pos(Pos::new(HPos::Left, vpos)),
+                    ),
+            ))?;
+
+            continue;
+        }
+
+        let text = format!("{label} {value:0.1}");
+        let (width, _) = chart
+            .plotting_area()
+            .estimate_text_size(&text, &style.text(font_size))?;
+        let half_width = width as i32 / 2;
+
+        // keep labels near the edges from being cut off
+        let x = chart.backend_coord(&(date, value)).0;
+        let hpos = if x - x_pixels.start < half_width {
+            HPos::Left
+        } else if x_pixels.end - x < half_width {
+            HPos::Right
+        } else {
+            HPos::Center
+        };
+
+        chart.draw_series(std::iter::once(
+            EmptyElement::at((date, value))
+                + Circle::new((0, 0), 4, color.filled())
+                + Text::new(
+                    text,
+                    (0, offset),
+                    style
+                        .text(font_size)
+                        .color(&color)
+                        .pos(Pos::new(hpos, vpos)),
+                ),
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Draws `layers` as areas stacked on top of each other, in order.
+fn draw_stacked<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    layers: Vec<Layer>,
+    percent: bool,
+    style: &PlotStyle,
+) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&style.theme.background())?;
+
+    let dates = layers
+        .first()
+        .map(|(_, _, values)| values.iter().map(|(date, _)| *date).collect_vec())
+        .unwrap_or_default();
+
+    let mut baseline = vec![0.0; dates.len()];
+    let mut stacked = Vec::new();
+
+    for (color, label, values) in layers {
+        let top = baseline
+            .iter()
+            .zip(&values)
+            .map(|(base, (_, value))| base + value)
+            .collect_vec();
+
+        stacked.push((color, label, baseline, top.clone()));
+        baseline = top;
+    }
+
+    let start_date = dates.first().copied().unwrap_or_default();
+    let end_date = dates.last().copied().unwrap_or_default();
+    let max_value = if percent {
+        100.0
+    } else {
+        max_float_iter(baseline.iter().copied())
+    };
+
+    let mut chart = ChartBuilder::on(&root)
+        .set_label_area_size(LabelAreaPosition::Left, style.font_size * 3)
+        .set_label_area_size(LabelAreaPosition::Bottom, style.font_size * 3)
+        .margin(10)
+        .build_cartesian_2d(start_date..end_date, 0.0..max_value)?;
+
+    chart
+        .configure_mesh()
+        .axis_style(style.theme.foreground())
+        .bold_line_style(style.theme.foreground().mix(0.2))
+        .light_line_style(style.theme.foreground().mix(0.08))
+        .x_labels(5)
+        .y_labels(5)
+        .label_style(style.text(style.font_size))
+        .x_label_formatter(&|date| date.format("%Y-%m-%d").to_string())
+        .y_label_formatter(&|value| {
+            if percent {
+                format!("{value:.0}%")
+            } else {
+                format!("{value:.0}")
+            }
+        })
+        .draw()?;
+
+    for (color, label, bottom, top) in stacked {
+        let outline = dates
+            .iter()
+            .copied()
+            .zip(top)
+            .chain(dates.iter().copied().zip(bottom).rev())
+            .collect_vec();
+
+        chart
+            .draw_series(std::iter::once(Polygon::new(
+                outline,
+                color.mix(0.8).filled(),
+            )))?
+            .label(label)
+            .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 20, y + 5)], color.filled()));
+    }
+
+    chart
+        .configure_series_labels()
+        .label_font(style.text(style.font_size))
+        .background_style(style.theme.background().mix(0.8))
+        .border_style(style.theme.foreground())
+        .draw()?;
+
+    root.present()?;
+
+    Ok(())
+}
+
+/// Draws a bar for each `(label, segments)`, with the segments stacked from the bottom.
+fn draw_bars<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    bars: Vec<Bar>,
+    draw_legend: bool,
+    note: Option<&str>,
+    style: &PlotStyle,
+) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&style.theme.background())?;
+
+    let max_total = max_float_iter(
+        bars.iter()
+            .map(|(_, segments)| segments.iter().map(|(_, _, value)| value).sum()),
+    );
+
+    let labels = bars.iter().map(|(label, _)| label.clone()).collect_vec();
+
+    let mut chart = ChartBuilder::on(&root)
+        .set_label_area_size(LabelAreaPosition::Left, style.font_size * 3)
+        .set_label_area_size(LabelAreaPosition::Bottom, style.font_size * 3)
+        .margin(10)
+        .build_cartesian_2d(
+            (0..bars.len() as i32 - 1).into_segmented(),
+            0.0..max_total * 1.15,
+        )?;
+
+    chart
+        .configure_mesh()
+        .axis_style(style.theme.foreground())
+        .bold_line_style(style.theme.foreground().mix(0.2))
+        .light_line_style(style.theme.foreground().mix(0.08))
+        .disable_x_mesh()
+        .y_labels(5)
+        .label_style(style.text(style.font_size))
+        .x_label_formatter(&|value| match value {
+            SegmentValue::CenterOf(i) => labels.get(*i as usize).cloned().unwrap_or_default(),
+            _ => String::new(),
+        })
+        .x_desc(note.unwrap_or_default())
+        .axis_desc_style(style.text(style.font_size * 7 / 10))
+        .draw()?;
+
+    let mut legend = Vec::new();
+
+    for (i, (_, segments)) in bars.iter().enumerate() {
+        let i = i as i32;
+        let mut bottom = 0.0;
+
+        for (color, label, value) in segments {
+            let top = bottom + value;
+
+            let mut bar = Rectangle::new(
+                [
+                    (SegmentValue::Exact(i), bottom),
+                    (SegmentValue::Exact(i + 1), top),
+                ],
+                color.filled(),
+            );
+            bar.set_margin(0, 0, 20, 20);
+
+            let series = chart.draw_series(std::iter::once(bar))?;
+
+            if draw_legend && !legend.contains(label) {
+                legend.push(label.clone());
+
+                let color = *color;
+                series.label(label).legend(move |(x, y)| {
+                    Rectangle::new([(x, y - 5), (x + 20, y + 5)], color.filled())
+                });
+            }
+
+            bottom = top;
+        }
+
+        let text_style = style
+            .text(style.font_size * 9 / 10)
+            .pos(Pos::new(HPos::Center, VPos::Bottom));
+
+        chart.draw_series(std::iter::once(Text::new(
+            format!("{bottom:0.1}kr"),
+            (SegmentValue::CenterOf(i), bottom),
+            text_style,
+        )))?;
+    }
+
+    if draw_legend {
+        chart
+            .configure_series_labels()
+            .label_font(style.text(style.font_size))
+            .background_style(style.theme.background().mix(0.8))
+            .border_style(style.theme.foreground())
             .draw()?;
     }
 
@@ -182,18 +1441,188 @@ fn draw(
     Ok(())
 }
 
-async fn upload(path: impl AsRef<Path>, state: &AppState, keep: bool) -> Result<String> {
-    let file_name = path.as_ref().file_name().unwrap().to_string_lossy();
-    let storage_key = format!("plots/{file_name}");
+/// Changes at least this big, in percent, get the strongest colour in the calendar.
+const CALENDAR_MAX_CHANGE: f64 = 5.0;
+/// Calendar cells are never smaller than this, in pixels.
+const CALENDAR_MIN_CELL: u32 = 4;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "maj", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+];
+
+/// Draws a column per week and a row per weekday, with a scale underneath.
+/// Days without a change are left grey.
+fn draw_calendar<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    days: Vec<(NaiveDate, Option<f64>)>,
+    cell: u32,
+    label_size: u32,
+    style: &PlotStyle,
+) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&style.theme.background())?;
+
+    let cell = cell as i32;
+    let left = label_size as i32 * 3;
+    let top = label_size as i32 * 2;
+    let text = |hpos, vpos| style.text(label_size).pos(Pos::new(hpos, vpos));
+
+    let Some((first_day, _)) = days.first() else {
+        return Ok(());
+    };
+    let offset = first_day.weekday().num_days_from_monday() as i32;
+
+    let position = |date: NaiveDate| {
+        let column = (date.ordinal0() as i32 + offset) / 7;
+        let row = date.weekday().num_days_from_monday() as i32;
+        (left + column * cell, top + row * cell)
+    };
+
+    for (date, change) in &days {
+        let (x, y) = position(*date);
+        let color = calendar_color(*change, style.theme);
+
+        root.draw(&Rectangle::new(
+            [(x + 1, y + 1), (x + cell - 1, y + cell - 1)],
+            color.filled(),
+        ))?;
+
+        if date.day() == 1 {
+            root.draw(&Text::new(
+                MONTHS[date.month0() as usize],
+                (x, top - label_size as i32 - 6),
+                text(HPos::Left, VPos::Top),
+            ))?;
+        }
+    }
+
+    for (row, weekday) in [(0, "mån"), (2, "ons"), (4, "fre")] {
+        root.draw(&Text::new(
+            weekday,
+            (left - 6, top + row * cell + cell / 2),
+            text(HPos::Right, VPos::Center),
+        ))?;
+    }
+
+    // a scale from the biggest decrease to the biggest increase
+    let steps = [-1.0, -0.75, -0.5, -0.25, 0.0, 0.25, 0.5, 0.75, 1.0];
+    let scale_y = top + cell * 8;
+    let scale_right = left + cell * 53 - label_size as i32 * 3;
+    let scale_left = scale_right - cell * steps.len() as i32;
+
+    for (i, step) in steps.iter().enumerate() {
+        let x = scale_left + i as i32 * cell;
+        let color = calendar_color(Some(step * CALENDAR_MAX_CHANGE), style.theme);
+
+        root.draw(&Rectangle::new(
+            [(x + 1, scale_y + 1), (x + cell - 1, scale_y + cell - 1)],
+            color.filled(),
+        ))?;
+    }
+
+    root.draw(&Text::new(
+        format!("-{CALENDAR_MAX_CHANGE}%"),
+        (scale_left - 6, scale_y + cell / 2),
+        text(HPos::Right, VPos::Center),
+    ))?;
+    root.draw(&Text::new(
+        format!("+{CALENDAR_MAX_CHANGE}%"),
+        (scale_right + 6, scale_y + cell / 2),
+        text(HPos::Left, VPos::Center),
+    ))?;
+
+    root.present()?;
+
+    Ok(())
+}
+
+/// Green for cheaper and red for more expensive, stronger the bigger the change.
+fn calendar_color(change: Option<f64>, theme: Theme) -> RGBColor {
+    let background = theme.background();
+    let empty = mix(background, theme.foreground(), 0.12);
+
+    let change = match change {
+        Some(change) if change.abs() >= 0.05 => change,
+        Some(_) => return mix(background, theme.foreground(), 0.3),
+        None => return empty,
+    };
+
+    let color = if change < 0.0 {
+        RGBColor(0x2e, 0xa0, 0x43)
+    } else {
+        RGBColor(0xda, 0x36, 0x33)
+    };
+
+    let strength = (change.abs() / CALENDAR_MAX_CHANGE).min(1.0);
+    mix(background, color, 0.3 + 0.7 * strength)
+}
+
+/// Blends `from` towards `to`, where an `amount` of 1 is just `to`.
+fn mix(from: RGBColor, to: RGBColor, amount: f64) -> RGBColor {
+    let blend = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * amount).round() as u8;
+    RGBColor(
+        blend(from.0, to.0),
+        blend(from.1, to.1),
+        blend(from.2, to.2),
+    )
+}
+
+/// Looks up an already rendered plot. `keep` skips the cache, since the plot should be saved again.
+fn cached(key: u64, keep: bool, state: &AppState) -> Option<Plot> {
+    if keep {
+        return None;
+    }
+
+    state.plot_cache.get(key)
+}
 
-    let data = tokio::fs::read(&path).await?;
-    state.storage.upload(&storage_key, data, true).await?;
+/// Uploads the plot if there's storage configured, so that it can be linked instead
+/// of attached, and caches it under `key`. With `keep`, the plot is also saved in `PLOTS_DIRECTORY`.
+async fn finish(
+    data: Vec<u8>,
+    key: Option<u64>,
+    style: &PlotStyle,
+    state: &AppState,
+    keep: bool,
+) -> Result<Plot> {
+    let file_name = format!("{}.{}", Uuid::new_v4(), style.format.extension());
 
-    if !keep {
-        tokio::fs::remove_file(path).await?;
+    if keep {
+        let dir = std::env::var("PLOTS_DIRECTORY").context("PLOTS_DIRECTORY must be set")?;
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(Path::new(&dir).join(&file_name), &data).await?;
     }
-    let url = state.storage.object_url(storage_key);
-    Ok(url)
+
+    let url = match &state.storage {
+        Some(storage) => {
+            let storage_key = format!("plots/{file_name}");
+            storage
+                .upload(
+                    &storage_key,
+                    data.clone(),
+                    style.format.content_type(),
+                    true,
+                )
+                .await?;
+
+            Some(storage.object_url(storage_key))
+        }
+        None => None,
+    };
+
+    let plot = Plot {
+        file_name,
+        data,
+        url,
+    };
+
+    if let Some(key) = key {
+        state.plot_cache.insert(key, &plot);
+    }
+
+    Ok(plot)
 }
 
 fn max_float_iter(iter: impl Iterator<Item = f64>) -> f64 {
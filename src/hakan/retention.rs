@@ -0,0 +1,124 @@
+//! Keeps the products table from growing forever.
+//!
+//! Products in reports older than the retention period are rolled up into the
+//! `daily_*` tables, which hold exactly what the history queries in [`super::db`]
+//! compute from them, and then deleted. The history series stay the same, but
+//! [`super::db::snapshot`] and the products export refuse dates that have been
+//! rolled up, since the individual products are gone.
+
+use anyhow::Result;
+use chrono::{TimeDelta, Utc};
+use tracing::info;
+
+use crate::AppState;
+
+/// Used if `HAKAN_RETENTION_DAYS` isn't set.
+const DEFAULT_RETENTION_DAYS: i64 = 365;
+/// Anomaly detection and forecasting need the recent products, so never prune those.
+const MIN_RETENTION_DAYS: i64 = 90;
+
+#[derive(Debug, Default)]
+pub struct Pruned {
+    pub reports: usize,
+    pub products: u64,
+}
+
+/// How many days of detailed products to keep.
+pub fn retention_days() -> i64 {
+    std::env::var("HAKAN_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+        .max(MIN_RETENTION_DAYS)
+}
+
+/// Rolls up and deletes the products of every report older than [`retention_days`].
+pub async fn downsample(state: &AppState) -> Result<Pruned> {
+    let cutoff = Utc::now().date_naive() - TimeDelta::days(retention_days());
+
+    let mut tx = state.db.begin().await?;
+
+    let report_ids = sqlx::query_scalar!(
+        "SELECT id FROM reports
+        WHERE date < $1
+          AND EXISTS (SELECT 1 FROM products WHERE products.report_id = reports.id)",
+        cutoff
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if report_ids.is_empty() {
+        return Ok(Pruned::default());
+    }
+
+    sqlx::query!(
+        "INSERT INTO daily_totals (report_id, price)
+        SELECT report_id, SUM(price)
+        FROM cheapest_products
+        WHERE report_id = ANY($1)
+        GROUP BY report_id",
+        &report_ids
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO daily_store_totals (report_id, store, price)
+        SELECT report_id, store, SUM(price)
+        FROM products
+        WHERE report_id = ANY($1)
+          AND NOT anomaly
+        GROUP BY report_id, store",
+        &report_ids
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO daily_ingredient_prices (report_id, ingredient_id, price)
+        SELECT report_id, ingredient_id, MIN(price)
+        FROM products
+        WHERE report_id = ANY($1)
+          AND NOT anomaly
+        GROUP BY report_id, ingredient_id",
+        &report_ids
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO daily_store_ingredient_prices (report_id, store, ingredient_id, price)
+        SELECT report_id, store, ingredient_id, MIN(price)
+        FROM products
+        WHERE report_id = ANY($1)
+          AND NOT anomaly
+        GROUP BY report_id, store, ingredient_id",
+        &report_ids
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let products = sqlx::query!(
+        "DELETE FROM products WHERE report_id = ANY($1)",
+        &report_ids
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+
+    let pruned = Pruned {
+        reports: report_ids.len(),
+        products,
+    };
+
+    info!(
+        reports = pruned.reports,
+        products = pruned.products,
+        %cutoff,
+        "downsampled old reports"
+    );
+
+    Ok(pruned)
+}
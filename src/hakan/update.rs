@@ -1,23 +1,51 @@
 use std::cmp::Ordering;
 
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{TimeDelta, Utc};
 use itertools::Itertools;
 use serenity::all::{ChannelId, Color, CreateEmbed, CreateMessage, Http, Message, RoleId};
 
 use crate::AppState;
 
+use super::{db::Range, plot::PlotStyle, ProductSource};
+
 pub const CHANNEL: ChannelId = ChannelId::new(1359621010726326432);
 pub const PING_ROLE: RoleId = RoleId::new(1359807749780930570);
 
+const FORECAST_DAYS: i64 = 14;
+/// Short enough for the plot to stay at daily resolution, see [`super::db::Resolution::for_span`].
+const PLOT_DAYS: i64 = 90;
+/// Discord's limit for all the embeds of a message together.
+const MESSAGE_MAX_LEN: usize = 6000;
+/// The diff is cut off at this length even if there's more room left in the message.
+const DIFF_MAX_LEN: usize = 1500;
+const MAIN_TITLE: &str = "☀️🍰 Håkanbörsen har öppnat för dagen! 🍰☀️";
+const COMPARISON_TITLE: &str = "🏪 Butikerna idag";
+
 #[tracing::instrument]
 pub async fn send(http: &Http, state: &AppState) -> Result<Message> {
-    let report = super::create_report(state).await?;
+    let mut report = super::create_report(state).await?;
     let last_report = super::db::last_products(state).await?;
 
+    super::anomaly::detect(&mut report, state).await?;
+
     super::save_report(&report, state).await?;
 
-    let url = super::plot::create_total(state, false).await?;
+    let today = Utc::now().date_naive();
+    let diff = super::diff::between(today - TimeDelta::days(1), today, state).await?;
+
+    let style = PlotStyle::configured();
+    let forecast = super::forecast::total(FORECAST_DAYS, state).await?;
+    let plot = super::plot::create_total(
+        state,
+        &Range::last_days(PLOT_DAYS),
+        forecast.as_ref(),
+        &style,
+        false,
+    )
+    .await?;
+    let comparison_plot =
+        super::plot::create_store_comparison(state, &report, true, &style, false).await?;
     let last_total_price: f64 = last_report.iter().map(|(_, product)| product.price).sum();
 
     let cheapest_products = report.cheapest().collect_vec();
@@ -36,45 +64,123 @@ pub async fn send(http: &Http, state: &AppState) -> Result<Message> {
                 .map(|(_, last_product)| product.price.total_cmp(&last_product.price))
                 .unwrap_or(Ordering::Equal);
 
+            let warning = if report.is_anomaly(*store, ingredient) {
+                "⚠️ "
+            } else {
+                ""
+            };
+
             (
                 format!(
-                    "{}{} `{:0.1}kr`",
+                    "{warning}{}{} `{:0.1}kr`",
                     get_emoji(last_ord),
                     ingredient.name,
                     product.price
                 ),
                 format!(
-                    "[{} {}]({}) ({}) ({}{})",
+                    "[{} {}]({}) ({}) ({}{}){}",
                     product.manufacturer_name,
                     product.name,
                     product.url,
                     store,
                     product.comparative_price,
                     product.comparative_price_text,
+                    match product.source {
+                        ProductSource::Category => "",
+                        ProductSource::Search => " 🔍",
+                    }
                 ),
                 false,
             )
-        });
+        })
+        .collect_vec();
+
+    let anomalies = report
+        .anomalies
+        .iter()
+        .sorted_by_key(|((store, ingredient_id), _)| (store.id(), *ingredient_id))
+        .map(|((store, ingredient_id), typical_price)| {
+            let product = &report.stores[store][ingredient_id];
+
+            format!(
+                "\n-# ⚠️ Ignorerade misstänkt pris på {} hos {store}: {}{} (brukar vara {typical_price:0.1}{})",
+                report.ingredients[ingredient_id].name,
+                product.comparative_price,
+                product.comparative_price_text,
+                product.comparative_price_text,
+            )
+        })
+        .join("");
+
+    let failures = report
+        .runs
+        .iter()
+        .filter(|run| run.error.is_some())
+        .map(|run| format!("\n-# ❌ {}: misslyckades", run.store))
+        .join("");
+
+    let outlook = match &forecast {
+        Some(forecast) if forecast.is_good_day_to_buy(total_price) => {
+            "\n🔮 Prognosen säger att håkan blir dyrare, idag är en bra dag att köpa!"
+        }
+        Some(_) => "\n🔮 Prognosen säger att håkan blir billigare, vänta gärna med att köpa.",
+        None => "",
+    };
+
+    let description = format!(
+        "<t:{}>\n# {}`{total_price:0.3}kr`{outlook}{anomalies}{failures}",
+        Utc::now().timestamp(),
+        get_emoji(total_price.total_cmp(&last_total_price))
+    );
+
+    // everything else in the message counts towards the limit that the diff has to fit in
+    let used = [MAIN_TITLE, COMPARISON_TITLE, description.as_str()]
+        .into_iter()
+        .chain(
+            fields
+                .iter()
+                .flat_map(|(name, value, _)| [name.as_str(), value.as_str()]),
+        )
+        .map(|text| text.chars().count())
+        .sum::<usize>();
 
     let embed = CreateEmbed::new()
-        .title(format!("☀️🍰 Håkanbörsen har öppnat för dagen! 🍰☀️"))
+        .title(MAIN_TITLE)
         .color(Color::DARK_GREEN)
-        .description(format!(
-            "<t:{}>\n# {}`{total_price:0.3}kr`",
-            Utc::now().timestamp(),
-            get_emoji(total_price.total_cmp(&last_total_price))
-        ))
+        .description(description)
         .fields(fields)
-        .image(url);
-
-    let msg = CHANNEL
-        .send_message(
-            http,
-            CreateMessage::new()
-                .content(format!("<@&{PING_ROLE}>"))
-                .add_embed(embed),
-        )
-        .await?;
+        .image(plot.embed_url());
+
+    let comparison = CreateEmbed::new()
+        .title(COMPARISON_TITLE)
+        .color(Color::DARK_GREEN)
+        .image(comparison_plot.embed_url());
+
+    let mut message = CreateMessage::new()
+        .content(format!("<@&{PING_ROLE}>"))
+        .add_embed(embed)
+        .add_embed(comparison)
+        .add_files(
+            plot.attachment()
+                .into_iter()
+                .chain(comparison_plot.attachment()),
+        );
+
+    if let Some(diff) = diff {
+        let title = format!("🔀 Ändringar sedan {}", diff.from);
+        let max_len = MESSAGE_MAX_LEN
+            .saturating_sub(used + title.chars().count())
+            .min(DIFF_MAX_LEN);
+
+        message = message.add_embed(
+            CreateEmbed::new()
+                .title(title)
+                .color(Color::DARK_GREEN)
+                .description(diff.render(max_len)),
+        );
+    }
+
+    let msg = CHANNEL.send_message(http, message).await?;
 
     Ok(msg)
 }
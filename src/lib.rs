@@ -18,13 +18,20 @@ pub mod storage;
 #[derive(Debug, Clone)]
 pub struct AppState {
     db: sqlx::PgPool,
-    storage: storage::Client,
+    /// Plots are attached to messages instead of uploaded if there's no storage.
+    storage: Option<storage::Client>,
     http: reqwest::Client,
+    plot_cache: hakan::plot::PlotCache,
 }
 
 impl AppState {
-    pub fn new(db: sqlx::PgPool, storage: storage::Client, http: reqwest::Client) -> Self {
-        Self { db, storage, http }
+    pub fn new(db: sqlx::PgPool, storage: Option<storage::Client>, http: reqwest::Client) -> Self {
+        Self {
+            db,
+            storage,
+            http,
+            plot_cache: Default::default(),
+        }
     }
 }
 
@@ -48,6 +55,12 @@ impl EventHandler for Bot {
             commands::hakan::role::register(),
             commands::hakan::recipe::register(),
             commands::hakan::wr::register(),
+            commands::hakan::shopping::register(),
+            commands::hakan::ingredient::register(),
+            commands::hakan::export::register(),
+            commands::hakan::status::register(),
+            commands::hakan::compare::register(),
+            commands::hakan::event::register(),
         ];
 
         /*
@@ -80,6 +93,16 @@ impl EventHandler for Bot {
                 "håkanroll" => commands::hakan::role::run(&command, &ctx, &self.state).await,
                 "håkanrecept" => commands::hakan::recipe::run(&command, &ctx, &self.state).await,
                 "håkanrekord" => commands::hakan::wr::run(&command, &ctx, &self.state).await,
+                "håkanhandla" => commands::hakan::shopping::run(&command, &ctx, &self.state).await,
+                "håkaningrediens" => {
+                    commands::hakan::ingredient::run(&command, &ctx, &self.state).await
+                }
+                "håkanexport" => commands::hakan::export::run(&command, &ctx, &self.state).await,
+                "håkanstatus" => commands::hakan::status::run(&command, &ctx, &self.state).await,
+                "håkanjämför" => {
+                    commands::hakan::compare::run(&command, &ctx, &self.state).await
+                }
+                "håkanhändelse" => commands::hakan::event::run(&command, &ctx, &self.state).await,
                 _ => Err(anyhow!("unknown command name")),
             };
 
@@ -99,6 +122,22 @@ impl EventHandler for Bot {
                     .await
                     .ok();
             }
+        } else if let Interaction::Component(component) = interaction {
+            info!(
+                custom_id = component.data.custom_id,
+                "received component interaction"
+            );
+
+            let res = match component.data.custom_id.as_str() {
+                commands::hakan::shopping::DM_BUTTON_ID => {
+                    commands::hakan::shopping::send_dm(&component, &ctx).await
+                }
+                _ => Err(anyhow!("unknown component id")),
+            };
+
+            if let Err(err) = res {
+                error!("failed to handle component: {err:#}");
+            }
         }
     }
 }
@@ -106,6 +145,9 @@ impl EventHandler for Bot {
 async fn setup_hakan_chron_job(http: Arc<Http>, state: AppState) -> Result<()> {
     let scheduler = JobScheduler::new().await?;
 
+    let maintenance_state = state.clone();
+    let maintenance_http = http.clone();
+
     let job = Job::new_async("0 0 7 * * *", move |_uuid, _l| {
         let http = http.clone();
         let state = state.clone();
@@ -120,7 +162,26 @@ async fn setup_hakan_chron_job(http: Arc<Http>, state: AppState) -> Result<()> {
     })
     .unwrap();
 
+    let maintenance_job = Job::new_async("0 0 3 * * Sun", move |_uuid, _l| {
+        let http = maintenance_http.clone();
+        let state = maintenance_state.clone();
+
+        Box::pin(async move {
+            info!("running weekly hakan maintenance");
+
+            if let Err(err) = hakan::retention::downsample(&state).await {
+                error!("failed to downsample old reports: {err:#}");
+            }
+
+            if let Err(err) = hakan::cleanup::delete_old_plots(&http, &state).await {
+                error!("failed to delete old plots: {err:#}");
+            }
+        })
+    })
+    .unwrap();
+
     scheduler.add(job).await?;
+    scheduler.add(maintenance_job).await?;
     scheduler.start().await?;
 
     Ok(())
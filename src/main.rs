@@ -32,24 +32,98 @@ async fn main() {
         .build()
         .expect("failed to build reqwest");
 
-    let api_key = env::var("SUPABASE_API_KEY").expect("SUPABASE_API_KEY must be set");
-
-    let storage = grimstabot::storage::Client::new(
-        "grimstabot".into(),
-        api_key.into(),
-        "https://fmqmtfvzpddscjxkntgn.supabase.co/storage/v1".into(),
-        http.clone(),
-    );
+    let storage = match env::var("SUPABASE_API_KEY") {
+        Ok(api_key) => Some(grimstabot::storage::Client::new(
+            "grimstabot".into(),
+            api_key.into(),
+            "https://fmqmtfvzpddscjxkntgn.supabase.co/storage/v1".into(),
+            http.clone(),
+        )),
+        Err(_) => {
+            info!("SUPABASE_API_KEY is not set, plots will be attached instead of uploaded");
+            None
+        }
+    };
 
     let state = grimstabot::AppState::new(db, storage, http);
 
-    if env::args().nth(1).as_deref() == Some("--test") {
+    if env::args().nth(1).as_deref() == Some("--export") {
+        let series = env::args()
+            .nth(2)
+            .and_then(|id| hakan::export::Series::from_id(&id))
+            .expect(
+                "usage: --export <total|butik|ingrediens|produkter> [--format csv|json] [--from YYYY-MM-DD] [--to YYYY-MM-DD] [--out <path>]",
+            );
+        let format = flag("--format")
+            .map(|id| hakan::export::Format::from_id(&id).expect("format must be csv or json"))
+            .unwrap_or(hakan::export::Format::Csv);
+        let range =
+            hakan::db::Range::from_dates(flag("--from").as_deref(), flag("--to").as_deref())
+                .unwrap();
+
+        let data = hakan::export::export(series, format, &range, &state)
+            .await
+            .unwrap();
+
+        match flag("--out") {
+            Some(path) => tokio::fs::write(path, data).await.unwrap(),
+            None => print!("{data}"),
+        }
+    } else if env::args().nth(1).as_deref() == Some("--import-cpi") {
+        let path = env::args().nth(2).expect("usage: --import-cpi <path>");
+        let count = hakan::cpi::import(path, &state).await.unwrap();
+
+        info!(count, "imported cpi");
+    } else if env::args().nth(1).as_deref() == Some("--import-history") {
+        let path = env::args()
+            .nth(2)
+            .expect("usage: --import-history <path> [--dry-run]");
+        let dry_run = env::args().any(|arg| arg == "--dry-run");
+        let summary = hakan::backfill::import(path, dry_run, &state)
+            .await
+            .unwrap();
+
+        info!(
+            reports = summary.reports,
+            products = summary.products,
+            replaced = summary.replaced,
+            skipped = summary.skipped,
+            dry_run,
+            "imported history"
+        );
+    } else if env::args().nth(1).as_deref() == Some("--downsample") {
+        let pruned = hakan::retention::downsample(&state).await.unwrap();
+
+        println!(
+            "pruned {} products from {} reports",
+            pruned.products, pruned.reports
+        );
+    } else if env::args().nth(1).as_deref() == Some("--discover") {
+        let store = env::args()
+            .nth(2)
+            .and_then(|id| hakan::Store::from_id(&id))
+            .expect("usage: --discover <coop|ica|willys|hemkop|mathem> <keyword>");
+        let keyword = env::args().nth(3).expect("keyword must be given");
+
+        let candidates = hakan::discover_categories(store, &keyword, &state)
+            .await
+            .unwrap();
+
+        for candidate in candidates {
+            println!("{} ({})", candidate.id, candidate.name);
+            for sample in candidate.samples {
+                println!("    {sample}");
+            }
+        }
+    } else if env::args().nth(1).as_deref() == Some("--test") {
         let report = hakan::create_report(&state).await.unwrap();
         let last_report = hakan::db::last_products(&state).await.unwrap();
         hakan::save_report(&report, &state).await.unwrap();
-        //hakan::plot::create_by_store(&state, true).await.unwrap();
+        //hakan::plot::create_by_store(&state, &Default::default(), &Default::default(), true).await.unwrap();
 
-        hakan::plot::create_by_store(&state, true).await.unwrap();
+        hakan::plot::create_by_store(&state, &Default::default(), &Default::default(), true)
+            .await
+            .unwrap();
         let last_total_price: f64 = last_report.iter().map(|(_, product)| product.price).sum();
 
         let cheapest_products = report.cheapest().collect_vec();
@@ -73,3 +147,8 @@ async fn main() {
         client.start().await.unwrap()
     }
 }
+
+/// The value following `name` on the command line.
+fn flag(name: &str) -> Option<String> {
+    env::args().skip_while(|arg| arg != name).nth(1)
+}
@@ -1,6 +1,26 @@
 use std::{fmt::Display, sync::Arc};
 
 use reqwest::Method;
+use serde::Deserialize;
+
+/// How many objects [`Client::list`] fetches per request.
+const LIST_PAGE_SIZE: usize = 1000;
+
+/// An object in the bucket, as returned by [`Client::list`].
+#[derive(Debug, Deserialize)]
+pub struct Object {
+    pub name: String,
+    /// RFC 3339 timestamp, `None` for folders.
+    pub created_at: Option<String>,
+    /// `None` for folders.
+    pub metadata: Option<ObjectMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ObjectMetadata {
+    /// In bytes.
+    pub size: u64,
+}
 
 /// A client to interact with the Supabase storage API.
 ///
@@ -41,11 +61,13 @@ impl Client {
         &self,
         key: impl Display,
         body: impl Into<reqwest::Body>,
+        content_type: &str,
         post: bool,
     ) -> anyhow::Result<()> {
         let method = if post { Method::POST } else { Method::PUT };
 
         self.request(self.object_path(key), method)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
             .body(body)
             .send()
             .await?
@@ -63,6 +85,34 @@ impl Client {
         Ok(())
     }
 
+    /// Lists everything directly under `prefix`, oldest first.
+    pub(crate) async fn list(&self, prefix: &str) -> anyhow::Result<Vec<Object>> {
+        let mut objects = Vec::new();
+
+        loop {
+            let page: Vec<Object> = self
+                .request(format!("/object/list/{}", self.bucket_name), Method::POST)
+                .json(&serde_json::json!({
+                    "prefix": prefix,
+                    "limit": LIST_PAGE_SIZE,
+                    "offset": objects.len(),
+                    "sortBy": { "column": "created_at", "order": "asc" },
+                }))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            let done = page.len() < LIST_PAGE_SIZE;
+            objects.extend(page);
+
+            if done {
+                return Ok(objects);
+            }
+        }
+    }
+
     pub(crate) fn object_url(&self, key: impl Display) -> String {
         format!("{}{}", self.base_url, self.object_path(key))
     }